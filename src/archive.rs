@@ -0,0 +1,177 @@
+//! # Archive
+//!
+//! Images inside comic book archives (CBZ) and ZIP files are shown as ordinary images. Each one
+//! is addressed by a virtual path made of the path of the archive, SEPARATOR and the name of the
+//! image inside the archive, for example `comic.cbz!/page01.jpg`.
+
+use crate::formats::Format;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+/// Separates the path of an archive from the name of an image inside it in a virtual path
+pub const SEPARATOR: &str = "!/";
+
+/// Number of bytes read from entries without a known extension to detect their format
+const SNIFF_LEN: u64 = 4096;
+
+/// Whether the path is a CBZ or ZIP archive, judging by its extension
+pub fn is_archive(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => {
+            let ext = ext.to_lowercase();
+            ext == "cbz" || ext == "zip"
+        }
+        None => false,
+    }
+}
+
+/// Builds the virtual path of an image inside an archive
+pub fn virtual_path(archive: &Path, name: &str) -> PathBuf {
+    PathBuf::from(format!("{}{}{}", archive.display(), SEPARATOR, name))
+}
+
+/// Splits a virtual path into the path of the archive and the name of the image inside it, None
+/// for paths that are not inside an archive
+pub fn split(path: &Path) -> Option<(PathBuf, String)> {
+    let path = path.to_string_lossy();
+    path.match_indices(SEPARATOR).find_map(|(i, _)| {
+        let archive = Path::new(&path[..i]);
+        if is_archive(archive) {
+            Some((
+                archive.to_path_buf(),
+                path[i + SEPARATOR.len()..].to_string(),
+            ))
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether the path is a virtual path of an image inside an archive
+pub fn is_archived(path: &Path) -> bool {
+    split(path).is_some()
+}
+
+/// The file on disk holding the image, the archive for virtual paths and the path itself otherwise
+pub fn real_path(path: &Path) -> PathBuf {
+    match split(path) {
+        Some((archive, _)) => archive,
+        None => path.to_path_buf(),
+    }
+}
+
+/// Lists the virtual path of every file inside the archive in natural order, along with its format
+/// when it is an image
+pub fn entries(archive: &Path) -> Result<Vec<(PathBuf, Option<Format>)>, String> {
+    let file = File::open(archive).map_err(|e| e.to_string())?;
+    let mut zip = ZipArchive::new(file).map_err(|e| e.to_string())?;
+    let mut entries = Vec::new();
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i).map_err(|e| e.to_string())?;
+        let name = entry.name().to_string();
+        // Resource forks macOS adds to archives are not images even with image extensions
+        if entry.is_dir() || name.starts_with("__MACOSX/") {
+            continue;
+        }
+        let extension = Path::new(&name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Format::from_extension);
+        let format = match extension {
+            Some(format) => Some(format),
+            None => {
+                let mut head = Vec::new();
+                (&mut entry)
+                    .take(SNIFF_LEN)
+                    .read_to_end(&mut head)
+                    .map_err(|e| e.to_string())?;
+                Format::sniff(&head)
+            }
+        };
+        entries.push((name, format));
+    }
+    entries.sort_by(|(a, _), (b, _)| natord::compare_ignore_case(a, b));
+    Ok(entries
+        .into_iter()
+        .map(|(name, format)| (virtual_path(archive, &name), format))
+        .collect())
+}
+
+/// Reads the bytes of the image at a virtual path out of its archive
+pub fn read(path: &Path) -> Result<Vec<u8>, String> {
+    let (archive, name) =
+        split(path).ok_or_else(|| format!("{} is not inside an archive", path.display()))?;
+    let file = File::open(&archive).map_err(|e| e.to_string())?;
+    let mut zip = ZipArchive::new(file).map_err(|e| e.to_string())?;
+    let mut entry = zip.by_name(&name).map_err(|e| e.to_string())?;
+    let mut bytes = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{entries, is_archive, read, real_path, split, virtual_path};
+    use crate::formats::Format;
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    #[test]
+    fn test_is_archive_by_extension() {
+        assert!(is_archive(Path::new("comics/issue1.CBZ")));
+        assert!(is_archive(Path::new("pages.zip")));
+        assert!(!is_archive(Path::new("page.jpg")));
+    }
+
+    #[test]
+    fn test_split_virtual_path() {
+        let path = virtual_path(Path::new("/comics/issue!/1.cbz"), "ch1/page01.jpg");
+        assert_eq!(path, PathBuf::from("/comics/issue!/1.cbz!/ch1/page01.jpg"));
+        assert_eq!(
+            split(&path),
+            Some((
+                PathBuf::from("/comics/issue!/1.cbz"),
+                "ch1/page01.jpg".to_string()
+            ))
+        );
+        assert_eq!(real_path(&path), PathBuf::from("/comics/issue!/1.cbz"));
+    }
+
+    #[test]
+    fn test_split_ignores_plain_paths() {
+        assert_eq!(split(Path::new("/images/wow!/page.jpg")), None);
+        assert_eq!(real_path(Path::new("page.jpg")), PathBuf::from("page.jpg"));
+    }
+
+    #[test]
+    fn test_entries_in_natural_order_and_read() {
+        let archive = std::env::temp_dir().join("riv_test_entries.cbz");
+        let mut zip = ZipWriter::new(File::create(&archive).unwrap());
+        for (name, bytes) in &[
+            ("page10.png", &b"\x89PNG\r\n\x1a\n"[..]),
+            ("page2.png", &b"\x89PNG\r\n\x1a\n"[..]),
+            ("notes.txt", &b"hello"[..]),
+            ("cover", &b"\xFF\xD8\xFF\xE0"[..]),
+        ] {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(bytes).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let found = entries(&archive).unwrap();
+        let expected = vec![
+            (virtual_path(&archive, "cover"), Some(Format::Jpeg)),
+            (virtual_path(&archive, "notes.txt"), None),
+            (virtual_path(&archive, "page2.png"), Some(Format::Png)),
+            (virtual_path(&archive, "page10.png"), Some(Format::Png)),
+        ];
+        assert_eq!(found, expected);
+        assert_eq!(read(&found[0].0).unwrap(), b"\xFF\xD8\xFF\xE0".to_vec());
+        std::fs::remove_file(archive).unwrap();
+    }
+}
@@ -0,0 +1,233 @@
+//! # Cache
+//!
+//! Keeps the textures of recently displayed images so flipping back to one of them needs no
+//! decoding. Textures are keyed by path, modification time and page, so an image edited on disk
+//! is loaded again. The least recently used textures are evicted once the cache is over its
+//! memory budget.
+
+use crate::archive::real_path;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Key identifies the texture of one page of an image as it was on disk
+#[derive(Clone, Debug, PartialEq)]
+pub struct Key {
+    /// path of the image, virtual paths included
+    pub path: PathBuf,
+    /// modification time of the file holding the image, None when it can't be read
+    pub modified: Option<SystemTime>,
+    /// page of the image the texture shows
+    pub page: usize,
+}
+
+impl Key {
+    /// Key of a page of the image at path as it is on disk now
+    pub fn new(path: &Path, page: usize) -> Self {
+        let modified = real_path(path)
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        Key {
+            path: path.to_path_buf(),
+            modified,
+            page,
+        }
+    }
+}
+
+/// TextureCache is a least recently used cache bounded by the memory its entries take up
+pub struct TextureCache<T> {
+    /// entries with their size in bytes, the most recently used last
+    entries: VecDeque<(Key, T, usize)>,
+    /// most bytes the entries may take up
+    budget: usize,
+    /// bytes taken up by the entries
+    used: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl<T> TextureCache<T> {
+    /// Creates an empty cache holding at most budget bytes
+    pub fn new(budget: usize) -> Self {
+        TextureCache {
+            entries: VecDeque::new(),
+            budget,
+            used: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Takes the entry for key out of the cache, counting a hit or a miss
+    pub fn take(&mut self, key: &Key) -> Option<T> {
+        match self.entries.iter().position(|(k, _, _)| k == key) {
+            Some(i) => {
+                self.hits += 1;
+                self.entries.remove(i).map(|(_, entry, size)| {
+                    self.used -= size;
+                    entry
+                })
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Borrows the entry for key, marking it as the most recently used and counting a hit or a
+    /// miss
+    pub fn get(&mut self, key: &Key) -> Option<&T> {
+        match self.entries.iter().position(|(k, _, _)| k == key) {
+            Some(i) => {
+                self.hits += 1;
+                let entry = self.entries.remove(i)?;
+                self.entries.push_back(entry);
+                self.entries.back().map(|(_, entry, _)| entry)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Adds an entry of size bytes as the most recently used, evicting the least recently used
+    /// entries until the cache is within its budget. Entries larger than the budget are dropped
+    pub fn insert(&mut self, key: Key, entry: T, size: usize) {
+        self.entries.retain(|(k, _, _)| k != &key);
+        if size > self.budget {
+            self.used = self.entries.iter().map(|(_, _, size)| size).sum();
+            return;
+        }
+        self.entries.push_back((key, entry, size));
+        self.used = self.entries.iter().map(|(_, _, size)| size).sum();
+        while self.used > self.budget {
+            match self.entries.pop_front() {
+                Some((_, _, size)) => self.used -= size,
+                None => break,
+            }
+        }
+    }
+
+    /// Evicts every page of the image at path
+    pub fn remove_path(&mut self, path: &Path) {
+        self.entries.retain(|(key, _, _)| key.path != path);
+        self.used = self.entries.iter().map(|(_, _, size)| size).sum();
+    }
+
+    /// Evicts every entry
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.used = 0;
+    }
+
+    /// Number of entries in the cache
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Bytes taken up by the entries
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// Most bytes the entries may take up
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Number of lookups that found their entry
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Number of lookups that did not find their entry
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Key, TextureCache};
+    use std::path::{Path, PathBuf};
+
+    fn key(path: &str, page: usize) -> Key {
+        Key {
+            path: PathBuf::from(path),
+            modified: None,
+            page,
+        }
+    }
+
+    #[test]
+    fn test_take_counts_hits_and_misses() {
+        let mut cache = TextureCache::new(100);
+        cache.insert(key("a.png", 0), 'a', 10);
+        assert_eq!(cache.take(&key("a.png", 1)), None);
+        assert_eq!(cache.take(&key("a.png", 0)), Some('a'));
+        assert_eq!(cache.take(&key("a.png", 0)), None);
+        assert_eq!((cache.hits(), cache.misses()), (1, 2));
+        assert_eq!(cache.used(), 0);
+    }
+
+    #[test]
+    fn test_insert_evicts_least_recently_used() {
+        let mut cache = TextureCache::new(25);
+        cache.insert(key("a.png", 0), 'a', 10);
+        cache.insert(key("b.png", 0), 'b', 10);
+        let a = cache.take(&key("a.png", 0)).unwrap();
+        cache.insert(key("a.png", 0), a, 10);
+        cache.insert(key("c.png", 0), 'c', 10);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.used(), 20);
+        assert_eq!(cache.take(&key("b.png", 0)), None);
+        cache.insert(key("huge.png", 0), 'h', 26);
+        assert_eq!(cache.take(&key("huge.png", 0)), None);
+    }
+
+    #[test]
+    fn test_get_marks_entry_as_recently_used() {
+        let mut cache = TextureCache::new(20);
+        cache.insert(key("a.png", 0), 'a', 10);
+        cache.insert(key("b.png", 0), 'b', 10);
+        assert_eq!(cache.get(&key("a.png", 0)), Some(&'a'));
+        cache.insert(key("c.png", 0), 'c', 10);
+        assert_eq!(cache.get(&key("b.png", 0)), None);
+        assert_eq!(cache.get(&key("a.png", 0)), Some(&'a'));
+        assert_eq!((cache.hits(), cache.misses()), (2, 1));
+    }
+
+    #[test]
+    fn test_remove_path_evicts_every_page() {
+        let mut cache = TextureCache::new(100);
+        cache.insert(key("a.tif", 0), 0, 10);
+        cache.insert(key("a.tif", 1), 1, 10);
+        cache.insert(key("b.png", 0), 2, 10);
+        cache.remove_path(Path::new("a.tif"));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.used(), 10);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_key_changes_with_modification_time() {
+        let path = std::env::temp_dir().join("riv_test_key.png");
+        std::fs::write(&path, b"a").unwrap();
+        let before = Key::new(&path, 0);
+        assert!(before.modified.is_some());
+        assert_eq!(before, Key::new(&path, 0));
+        assert_ne!(before, Key::new(&path, 1));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(Key::new(&path, 0).modified, None);
+    }
+}
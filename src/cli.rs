@@ -2,18 +2,26 @@
 //!
 //! The cli module is used for setting up the command line app and parsing the arguments.
 
+use crate::decode::ColorManager;
+use crate::formats::{FormatFilter, Skipped};
+use crate::scan::Scanner;
+use crate::slideshow::Slideshow;
 use crate::sort::SortOrder;
+use crate::stdin;
 use clap::{App, Arg};
-use glob::glob;
 use std::env::current_dir;
 use std::fs::File;
 use std::io::{prelude::*, BufReader};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 /// Args contains the arguments that have been successfully parsed by the clap cli app
 pub struct Args {
-    /// files is the vector of image file paths that match the supplied or default glob
+    /// files is the vector of image file paths read from the list file or standard input
     pub files: Vec<PathBuf>,
+    /// scanner finds the images matching the supplied or default glob in the background, None
+    /// when images come from a list file or standard input
+    pub scanner: Option<Scanner>,
     /// dest_folder is the supplied or default folder for moving files
     pub dest_folder: PathBuf,
     /// provides the SortOrder specified by the user
@@ -24,10 +32,25 @@ pub struct Args {
     pub shuffle: bool,
     /// maximum length of files to display
     pub max_length: usize,
+    /// number of images before and after the current image to decode in the background
+    pub prefetch: usize,
+    /// megabytes of memory the textures of recently viewed images may take up
+    pub cache_size: usize,
     /// Start in fullscreen mode
     pub fullscreen: bool,
+    /// Rotate images upright from their EXIF orientation
+    pub auto_rotate: bool,
+    /// Converts images with an embedded color profile to the display profile, None when color
+    /// management is off
+    pub color_manager: Option<ColorManager>,
     /// New base directory defaults to std::env::current_dir
     pub base_dir: PathBuf,
+    /// formats of images to accept when finding images
+    pub formats: FormatFilter,
+    /// paths that were found but not added to files
+    pub skipped: Skipped,
+    /// Slideshow to start with, None when not requested
+    pub slideshow: Option<Slideshow>,
 }
 
 /// cli sets up the command line app and parses the arguments, using clap.
@@ -39,7 +62,7 @@ pub fn cli() -> Result<Args, String> {
         .arg(
             Arg::with_name("paths")
                 .default_value("*")
-                .help("The directory or files to search for image files. A glob can be used here."),
+                .help("The directory or files to search for image files. A glob can be used here, - reads one image from standard input."),
         )
         .arg(
             Arg::with_name("dest-folder")
@@ -66,7 +89,14 @@ pub fn cli() -> Result<Args, String> {
                 .long("list-file")
                 .takes_value(true)
                 .case_insensitive(true)
-                .help("Load images from a list in a text file"),
+                .help("Load images from a list in a text file, - reads the list from standard input"),
+        )
+        .arg(
+            Arg::with_name("null")
+                .short("0")
+                .long("null")
+                .takes_value(false)
+                .help("Entries of the list file are separated by NUL bytes instead of newlines, as printed by find -print0"),
         )
         .arg(
             Arg::with_name("reverse")
@@ -94,6 +124,32 @@ pub fn cli() -> Result<Args, String> {
                 .multiple(false)
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("cache-size")
+                .default_value("256")
+                .long("cache-size")
+                .help("Megabytes of memory kept for the textures of recently viewed images [0 turns the cache off]")
+                .multiple(false)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("prefetch")
+                .default_value("2")
+                .long("prefetch")
+                .help("The number of images before and after the current image to decode in the background [0 turns prefetching off]")
+                .multiple(false)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("formats")
+                .long("formats")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .help(
+                    "Comma separated image formats to accept, prefix a format with - to exclude it \
+                     [jpeg, png, bmp, webp, gif, tiff, svg, raw, exr, hdr, tga, pnm, ico, qoi]",
+                ),
+        )
         .arg(
             Arg::with_name("fullscreen")
                 .takes_value(false)
@@ -101,6 +157,38 @@ pub fn cli() -> Result<Args, String> {
                 .short("F")
                 .help("Start app in fullscreen mode"),
         )
+        .arg(
+            Arg::with_name("no-auto-rotate")
+                .takes_value(false)
+                .long("no-auto-rotate")
+                .help("Show images as stored instead of rotating them upright from their EXIF orientation"),
+        )
+        .arg(
+            Arg::with_name("color-manage")
+                .takes_value(false)
+                .long("color-manage")
+                .help("Convert images with an embedded ICC profile to sRGB"),
+        )
+        .arg(
+            Arg::with_name("display-profile")
+                .takes_value(true)
+                .long("display-profile")
+                .help("Convert images with an embedded ICC profile to this ICC profile instead of sRGB, implies --color-manage"),
+        )
+        .arg(
+            Arg::with_name("slideshow")
+                .takes_value(true)
+                .long("slideshow")
+                .value_name("secs")
+                .help("Start a slideshow showing each image for this many seconds"),
+        )
+        .arg(
+            Arg::with_name("loop")
+                .takes_value(false)
+                .long("loop")
+                .requires("slideshow")
+                .help("Start the slideshow over after the last image"),
+        )
         .get_matches();
 
     // find current directory so glob provided can be relative
@@ -109,23 +197,33 @@ pub fn cli() -> Result<Args, String> {
         Err(_) => PathBuf::new(),
     };
 
+    let formats = match matches.value_of("formats") {
+        Some(formats) => FormatFilter::from_str(formats)?,
+        None => FormatFilter::default(),
+    };
+    let mut skipped = Skipped::default();
+    let mut scanner = None;
+
     match matches.value_of("list-file") {
         Some(list_file_path) => {
-            let file = match File::open(list_file_path) {
-                Ok(f) => f,
-                Err(e) => return Err(format!("Error opening file-list: {}", e)),
-            };
-            let reader = BufReader::new(file);
-
-            for line in reader.lines() {
-                match line {
-                    Ok(p) => {
-                        if !p.starts_with("#") {
-                            push_image_path(&mut files, PathBuf::from(p));
-                        }
-                    }
+            let null = matches.is_present("null");
+            let entries = if list_file_path == "-" {
+                let stdin = std::io::stdin();
+                read_list(stdin.lock(), null, &base_dir)?
+            } else {
+                let file = match File::open(list_file_path) {
+                    Ok(f) => f,
                     Err(e) => return Err(format!("Error opening file-list: {}", e)),
-                }
+                };
+                // Relative entries are relative to the list file, not to where riv was started
+                let list_dir = match Path::new(list_file_path).parent() {
+                    Some(dir) => base_dir.join(dir),
+                    None => base_dir.clone(),
+                };
+                read_list(BufReader::new(file), null, &list_dir)?
+            };
+            for path in entries {
+                formats.push(&mut files, &mut skipped, path);
             }
         }
         None => {
@@ -133,17 +231,15 @@ pub fn cli() -> Result<Args, String> {
                 Some(v) => v,
                 None => panic!("No value for paths!"),
             };
-            let path_glob = crate::path_to_glob(&base_dir, path_glob)?;
-            // find new base directory
-            if let Ok(new_base_dir) = crate::new_base_dir(&path_glob) {
-                base_dir = new_base_dir;
-            }
-            let glob_matches = glob(&path_glob.to_string_lossy()).map_err(|e| e.to_string())?;
-            for path in glob_matches {
-                match path {
-                    Ok(p) => push_image_path(&mut files, p),
-                    Err(e) => eprintln!("Path not processable {}", e),
+            if path_glob == "-" {
+                files.push(stdin::read()?);
+            } else {
+                let path_glob = crate::path_to_glob(&base_dir, path_glob)?;
+                // find new base directory
+                if let Ok(new_base_dir) = crate::new_base_dir(&path_glob) {
+                    base_dir = new_base_dir;
                 }
+                scanner = Some(Scanner::start(&path_glob, formats.clone())?);
             }
         }
     };
@@ -165,27 +261,111 @@ pub fn cli() -> Result<Args, String> {
     let shuffle = matches.is_present("shuffle");
 
     let max_length = value_t!(matches, "max-number-images", usize).unwrap_or(0);
+    let prefetch = value_t!(matches, "prefetch", usize).unwrap_or(2);
+    let cache_size = value_t!(matches, "cache-size", usize).unwrap_or(256);
     let fullscreen = matches.is_present("fullscreen");
+    let auto_rotate = !matches.is_present("no-auto-rotate");
+    let color_manager = match matches.value_of("display-profile") {
+        Some(profile) => Some(ColorManager::from_file(&PathBuf::from(profile))?),
+        None if matches.is_present("color-manage") => Some(ColorManager::srgb()),
+        None => None,
+    };
+    let slideshow = match matches.value_of("slideshow") {
+        Some(seconds) => {
+            let mut slideshow = Slideshow::parse(seconds)?;
+            slideshow.looping |= matches.is_present("loop");
+            Some(slideshow)
+        }
+        None => None,
+    };
 
     Ok(Args {
         files,
+        scanner,
         dest_folder,
         sort_order,
         reverse,
         shuffle,
         max_length,
+        prefetch,
+        cache_size,
         fullscreen,
+        auto_rotate,
+        color_manager,
         base_dir,
+        formats,
+        skipped,
+        slideshow,
     })
 }
 
-pub(crate) fn push_image_path(v: &mut Vec<PathBuf>, p: PathBuf) {
-    if let Some(ext) = p.extension() {
-        if let Some(ext_str) = ext.to_str() {
-            let low = ext_str.to_string().to_lowercase();
-            if low == "jpg" || low == "jpeg" || low == "png" || low == "bmp" || low == "webp" {
-                v.push(p)
-            }
+/// Reads the paths in a list file, one per line or separated by NUL bytes when null is set.
+/// Lines starting with # are comments, NUL separated entries have no comments since file names
+/// may start with #. Relative paths are resolved against dir
+fn read_list<R: BufRead>(mut reader: R, null: bool, dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let delimiter = if null { b'\0' } else { b'\n' };
+    let mut paths = Vec::new();
+    let mut entry = Vec::new();
+    loop {
+        entry.clear();
+        let read = reader
+            .read_until(delimiter, &mut entry)
+            .map_err(|e| format!("Error opening file-list: {}", e))?;
+        if read == 0 {
+            break;
         }
+        if entry.last() == Some(&delimiter) {
+            entry.pop();
+        }
+        if !null && entry.last() == Some(&b'\r') {
+            entry.pop();
+        }
+        if entry.is_empty() || (!null && entry.starts_with(b"#")) {
+            continue;
+        }
+        paths.push(dir.join(path_from_bytes(&entry)));
+    }
+    Ok(paths)
+}
+
+/// Converts an entry of a list file to a path, keeping file names that are not UTF-8 on Unix
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+/// Converts an entry of a list file to a path, file names must be UTF-8 outside of Unix
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_list;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_read_list_skips_comments_and_resolves_relative() {
+        let list = &b"# comment\nb.png\r\n\n/abs/a.jpg\n"[..];
+        let paths = read_list(list, false, Path::new("/lists")).unwrap();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("/lists/b.png"), PathBuf::from("/abs/a.jpg")]
+        );
+    }
+
+    #[test]
+    fn test_read_list_splits_on_nul() {
+        let list = &b"new\nline.png\0#hash.png\0"[..];
+        let paths = read_list(list, true, Path::new("/cwd")).unwrap();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("/cwd/new\nline.png"),
+                PathBuf::from("/cwd/#hash.png")
+            ]
+        );
     }
 }
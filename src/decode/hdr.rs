@@ -0,0 +1,96 @@
+//! High dynamic range (OpenEXR, Radiance HDR) and high bit depth (16 bit PNG) images keep their
+//! pixels as linear floats, so they can be mapped to 8 bits again at another exposure or gamma
+//! without reading the file a second time
+
+use image::DynamicImage;
+
+/// Gamma 8 bit pixels are encoded with, mapping at this gamma and no exposure change shows high bit
+/// depth images as they are stored
+pub const DEFAULT_GAMMA: f32 = 2.2;
+
+/// Hdr holds the pixels of an image as linear floats in RGBA order
+pub struct Hdr {
+    pixels: Vec<f32>,
+}
+
+impl Hdr {
+    /// Keeps the pixels of float and 16 bit images, None for images with 8 bits per channel
+    pub fn from_image(image: &DynamicImage) -> Option<Hdr> {
+        let pixels = match image {
+            // Float images are already linear
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+                image.to_rgba32f().into_raw()
+            }
+            DynamicImage::ImageLuma16(_)
+            | DynamicImage::ImageLumaA16(_)
+            | DynamicImage::ImageRgb16(_)
+            | DynamicImage::ImageRgba16(_) => {
+                let mut pixels = image.to_rgba32f().into_raw();
+                // Integer images are gamma encoded, alpha is not
+                for pixel in pixels.chunks_exact_mut(4) {
+                    for channel in &mut pixel[..3] {
+                        *channel = channel.powf(DEFAULT_GAMMA);
+                    }
+                }
+                pixels
+            }
+            _ => return None,
+        };
+        Some(Hdr { pixels })
+    }
+
+    /// Number of bytes the float pixels take up in memory
+    pub fn memory_size(&self) -> usize {
+        // 4 bytes per f32
+        self.pixels.len() * 4
+    }
+
+    /// Maps the pixels to 8 bits per channel, scaling the light by 2^exposure and encoding it with
+    /// gamma. Light brighter than the display can show is clipped
+    pub fn tone_map(&self, exposure: f32, gamma: f32) -> Vec<u8> {
+        let scale = exposure.exp2();
+        let to_u8 = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        self.pixels
+            .chunks_exact(4)
+            .flat_map(|pixel| {
+                let channel = |value: f32| to_u8((value * scale).max(0.0).powf(1.0 / gamma));
+                vec![
+                    channel(pixel[0]),
+                    channel(pixel[1]),
+                    channel(pixel[2]),
+                    to_u8(pixel[3]),
+                ]
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Hdr, DEFAULT_GAMMA};
+    use image::{DynamicImage, ImageBuffer, Rgb, Rgba};
+
+    #[test]
+    fn test_eight_bit_images_have_no_hdr() {
+        let image = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(1, 1, Rgb([1, 2, 3])));
+        assert!(Hdr::from_image(&image).is_none());
+    }
+
+    #[test]
+    fn test_sixteen_bit_round_trips_at_default_gamma() {
+        let pixel = Rgba([0x8080, 0x4040, 0xFFFF, 0xFFFF]);
+        let image = DynamicImage::ImageRgba16(ImageBuffer::from_pixel(1, 1, pixel));
+        let hdr = Hdr::from_image(&image).unwrap();
+        assert_eq!(hdr.tone_map(0.0, DEFAULT_GAMMA), vec![128, 64, 255, 255]);
+    }
+
+    #[test]
+    fn test_exposure_scales_linear_light() {
+        let pixel = Rgb([0.25f32, 2.0, -1.0]);
+        let image = DynamicImage::ImageRgb32F(ImageBuffer::from_pixel(1, 1, pixel));
+        let hdr = Hdr::from_image(&image).unwrap();
+        assert_eq!(hdr.tone_map(0.0, 1.0), vec![64, 255, 0, 255]);
+        assert_eq!(hdr.tone_map(1.0, 1.0), vec![128, 255, 0, 255]);
+        assert_eq!(hdr.tone_map(-3.0, 1.0), vec![8, 64, 0, 255]);
+    }
+}
@@ -0,0 +1,119 @@
+//! Color management of images with an embedded ICC profile. Their pixels are converted from the
+//! embedded profile to the profile of the display, sRGB unless the user supplies one
+
+use super::Image;
+use qcms::{DataType, Intent, Profile, Transform};
+use std::path::Path;
+
+/// ColorManager converts images to the color profile of the display
+pub struct ColorManager {
+    display: Box<Profile>,
+}
+
+impl ColorManager {
+    /// Converts images to sRGB, the color space of most displays
+    pub fn srgb() -> Self {
+        Self::with_display(Profile::new_sRGB())
+    }
+
+    /// Converts images to the ICC profile in the file at path
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("Error opening display profile {}: {}", path.display(), e))?;
+        let display = Profile::new_from_slice(&bytes, false)
+            .ok_or_else(|| format!("{} is not a valid ICC profile", path.display()))?;
+        Ok(Self::with_display(display))
+    }
+
+    fn with_display(mut display: Box<Profile>) -> Self {
+        // Speeds up every transform to this profile
+        display.precache_output_transform();
+        ColorManager { display }
+    }
+
+    /// Converts every frame of the image from its embedded profile to the display profile
+    /// Returns whether the image was converted, images without a usable RGB profile are left as
+    /// they are
+    pub fn convert(&self, image: &mut Image) -> bool {
+        let profile = match image
+            .icc_profile
+            .as_ref()
+            .filter(|icc| is_rgb(icc))
+            .and_then(|icc| Profile::new_from_slice(icc, false))
+        {
+            Some(profile) => profile,
+            None => return false,
+        };
+        self.convert_from(&profile, image)
+    }
+
+    fn convert_from(&self, profile: &Profile, image: &mut Image) -> bool {
+        // Nothing to do, the pixels are already in the display profile
+        if profile.is_sRGB() && self.display.is_sRGB() {
+            return true;
+        }
+        let transform =
+            match Transform::new(profile, &self.display, DataType::RGBA8, Intent::default()) {
+                Some(transform) => transform,
+                None => return false,
+            };
+        for frame in &mut image.frames {
+            transform.apply(&mut frame.pixels);
+        }
+        true
+    }
+}
+
+/// Whether the color space in the header of the ICC profile is RGB, gray and CMYK profiles don't
+/// describe the RGBA pixels of decoded frames
+fn is_rgb(icc: &[u8]) -> bool {
+    icc.get(16..20) == Some(b"RGB ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ColorManager;
+    use crate::decode::{Frame, Image, DEFAULT_DELAY};
+    use qcms::Profile;
+
+    fn image(icc_profile: Option<Vec<u8>>) -> Image {
+        Image {
+            icc_profile,
+            ..Image::still(Frame {
+                width: 1,
+                height: 1,
+                pixels: vec![200, 100, 50, 255],
+                delay: DEFAULT_DELAY,
+            })
+        }
+    }
+
+    #[test]
+    fn test_convert_skips_invalid_profiles() {
+        let manager = ColorManager::srgb();
+        let mut untagged = image(None);
+        assert!(!manager.convert(&mut untagged));
+        let mut invalid = image(Some(b"not a profile".to_vec()));
+        assert!(!manager.convert(&mut invalid));
+        assert_eq!(invalid.frames[0].pixels, vec![200, 100, 50, 255]);
+    }
+
+    #[test]
+    fn test_convert_skips_gray_profiles() {
+        let manager = ColorManager::srgb();
+        let mut icc = vec![0; 128];
+        icc[0..4].copy_from_slice(&128u32.to_be_bytes());
+        icc[16..20].copy_from_slice(b"GRAY");
+        let mut tagged = image(Some(icc));
+        assert!(!manager.convert(&mut tagged));
+        assert_eq!(tagged.frames[0].pixels, vec![200, 100, 50, 255]);
+    }
+
+    #[test]
+    fn test_convert_from_srgb_keeps_pixels() {
+        let manager = ColorManager::srgb();
+        let mut tagged = image(None);
+        assert!(manager.convert_from(&Profile::new_sRGB(), &mut tagged));
+        assert_eq!(tagged.frames[0].pixels, vec![200, 100, 50, 255]);
+    }
+}
@@ -0,0 +1,136 @@
+//! Decoding of Windows icons and cursors (ICO and CUR). An icon holds the same picture at several
+//! sizes, each size is shown as a page with the largest size first
+
+use super::{Frame, Image, DEFAULT_DELAY};
+use image::codecs::ico::IcoDecoder;
+use image::DynamicImage;
+use std::convert::TryInto;
+use std::io::Cursor;
+
+/// Length of the header at the start of every icon
+const HEADER_LEN: usize = 6;
+/// Length of the directory entry describing each size
+const ENTRY_LEN: usize = 16;
+
+/// Entry describes one size stored in an icon
+struct Entry<'a> {
+    width: u32,
+    height: u32,
+    bits_per_pixel: u16,
+    /// the directory entry as it is stored in the file
+    raw: &'a [u8],
+    /// the PNG or bitmap data of this size
+    data: &'a [u8],
+}
+
+/// Whether the bytes start with the header of an icon or a cursor with at least one size
+pub fn is_icon(bytes: &[u8]) -> bool {
+    bytes.len() >= HEADER_LEN
+        && bytes[0..2] == [0, 0]
+        && (bytes[2..4] == [1, 0] || bytes[2..4] == [2, 0])
+        && bytes[4..6] != [0, 0]
+}
+
+/// Decodes a single size of an icon, page 0 is the largest size
+pub fn decode_size(bytes: &[u8], page: usize) -> Result<Image, String> {
+    let entries = sizes(bytes)?;
+    let entry = entries
+        .get(page)
+        .ok_or_else(|| format!("page {} is past the last page {}", page + 1, entries.len()))?;
+    let decoder =
+        IcoDecoder::new(Cursor::new(single_entry(bytes, entry))).map_err(|e| e.to_string())?;
+    let image = DynamicImage::from_decoder(decoder)
+        .map_err(|e| e.to_string())?
+        .to_rgba8();
+    Ok(Image {
+        pages: entries.len(),
+        ..Image::still(Frame {
+            width: image.width(),
+            height: image.height(),
+            pixels: image.into_raw(),
+            delay: DEFAULT_DELAY,
+        })
+    })
+}
+
+/// Reads the directory of the icon, sorted from the largest size and deepest color to the smallest
+fn sizes(bytes: &[u8]) -> Result<Vec<Entry<'_>>, String> {
+    if !is_icon(bytes) {
+        return Err("not an icon".to_string());
+    }
+    let count = usize::from(u16::from_le_bytes([bytes[4], bytes[5]]));
+    let mut entries = Vec::with_capacity(count);
+    for i in 0..count {
+        let start = HEADER_LEN + i * ENTRY_LEN;
+        let raw = bytes
+            .get(start..start + ENTRY_LEN)
+            .ok_or_else(|| "icon directory is truncated".to_string())?;
+        let length = u32::from_le_bytes(raw[8..12].try_into().unwrap()) as usize;
+        let offset = u32::from_le_bytes(raw[12..16].try_into().unwrap()) as usize;
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| bytes.get(offset..end))
+            .ok_or_else(|| format!("icon size {} is truncated", i + 1))?;
+        entries.push(Entry {
+            // A width or height of 0 means 256 pixels
+            width: if raw[0] == 0 { 256 } else { u32::from(raw[0]) },
+            height: if raw[1] == 0 { 256 } else { u32::from(raw[1]) },
+            bits_per_pixel: u16::from_le_bytes([raw[6], raw[7]]),
+            raw,
+            data,
+        });
+    }
+    entries
+        .sort_by_key(|entry| std::cmp::Reverse((entry.width * entry.height, entry.bits_per_pixel)));
+    Ok(entries)
+}
+
+/// Builds an icon holding only the given size, so the decoder picks it over the others
+fn single_entry(bytes: &[u8], entry: &Entry) -> Vec<u8> {
+    let offset = (HEADER_LEN + ENTRY_LEN) as u32;
+    let mut icon = Vec::with_capacity(HEADER_LEN + ENTRY_LEN + entry.data.len());
+    icon.extend_from_slice(&bytes[0..4]);
+    icon.extend_from_slice(&1u16.to_le_bytes());
+    icon.extend_from_slice(&entry.raw[0..12]);
+    icon.extend_from_slice(&offset.to_le_bytes());
+    icon.extend_from_slice(entry.data);
+    icon
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_size, is_icon};
+
+    const SIZES: &[u8] = include_bytes!("../../tests/fixtures/sizes.ico");
+    const CURSOR: &[u8] = include_bytes!("../../tests/fixtures/pointer.cur");
+
+    #[test]
+    fn test_is_icon_accepts_icons_and_cursors() {
+        assert!(is_icon(SIZES));
+        assert!(is_icon(CURSOR));
+        assert!(!is_icon(b"\0\0\x01\0\0\0"));
+        assert!(!is_icon(b"BM"));
+    }
+
+    #[test]
+    fn test_sizes_are_pages_from_largest() {
+        let largest = decode_size(SIZES, 0).unwrap();
+        assert_eq!(largest.pages, 2);
+        assert_eq!((largest.frames[0].width, largest.frames[0].height), (2, 2));
+        assert_eq!(&largest.frames[0].pixels[0..4], &[255, 0, 0, 255]);
+        let smallest = decode_size(SIZES, 1).unwrap();
+        assert_eq!(
+            (smallest.frames[0].width, smallest.frames[0].height),
+            (1, 1)
+        );
+        assert_eq!(smallest.frames[0].pixels, vec![0, 0, 255, 255]);
+        assert!(decode_size(SIZES, 2).is_err());
+    }
+
+    #[test]
+    fn test_decodes_cursor() {
+        let cursor = decode_size(CURSOR, 0).unwrap();
+        assert_eq!(cursor.pages, 1);
+        assert_eq!(cursor.frames[0].pixels.len(), 2 * 2 * 4);
+    }
+}
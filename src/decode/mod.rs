@@ -0,0 +1,610 @@
+//! # Decode
+//!
+//! The decode module reads image files into frames of RGBA pixels ready to be uploaded as
+//! textures. Animated images (GIF, WebP and APNG) keep every frame along with how long each frame
+//! is displayed and how many times the animation plays. Files with several pages (TIFF) are
+//! decoded one page at a time, the same goes for the sizes of icons (ICO and CUR), while camera
+//! RAW files show the JPEG preview embedded in them.
+//! Vector images (SVG) keep their parsed document so they can be rasterized again at the scale
+//! they are displayed at, and high dynamic range images (EXR, HDR and 16 bit) keep their float
+//! pixels so they can be mapped to 8 bits again at another exposure.
+
+mod hdr;
+mod icc;
+mod ico;
+mod raw;
+mod svg;
+mod tif;
+
+pub use self::hdr::{Hdr, DEFAULT_GAMMA};
+pub use self::icc::ColorManager;
+pub use self::ico::is_icon;
+pub use self::raw::is_raw;
+pub use self::svg::{is_svg, Svg};
+
+use crate::archive;
+use crate::stdin::{self, is_stdin};
+use image::codecs::gif::GifDecoder;
+use image::codecs::jpeg::JpegDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::tiff::TiffDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::imageops::{self, FilterType};
+use image::io::Reader;
+use image::{AnimationDecoder, Frames, ImageBuffer, ImageDecoder, ImageFormat, Rgba};
+use std::convert::TryInto;
+use std::io::Cursor;
+use std::path::Path;
+use std::time::Duration;
+
+/// Browsers treat frame delays this short as unset, showing the frame for DEFAULT_DELAY instead
+const MIN_DELAY: Duration = Duration::from_millis(10);
+/// Delay used for frames that do not specify a usable delay
+const DEFAULT_DELAY: Duration = Duration::from_millis(100);
+
+/// Frame is a single fully composited picture of an image
+pub struct Frame {
+    /// width of the frame in pixels
+    pub width: u32,
+    /// height of the frame in pixels
+    pub height: u32,
+    /// pixels of the frame in RGBA order, 4 bytes per pixel
+    pub pixels: Vec<u8>,
+    /// delay is how long the frame is displayed before moving to the next one
+    pub delay: Duration,
+}
+
+impl Frame {
+    /// Number of bytes in a row of pixels
+    pub fn pitch(&self) -> usize {
+        self.width as usize * 4
+    }
+
+    /// Resamples the frame to width by height pixels, None when its pixels don't match its size
+    pub fn resized(&self, width: u32, height: u32) -> Option<Frame> {
+        let image =
+            ImageBuffer::<Rgba<u8>, &[u8]>::from_raw(self.width, self.height, &self.pixels)?;
+        let resized = imageops::resize(&image, width, height, FilterType::Triangle);
+        Some(Frame {
+            width,
+            height,
+            pixels: resized.into_raw(),
+            delay: self.delay,
+        })
+    }
+
+    /// Turns the frame upright from its EXIF orientation, flipping before rotating like the
+    /// renderer does. None when its pixels don't match its size
+    pub fn oriented(self, orientation: u32) -> Option<Frame> {
+        let mut image =
+            ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(self.width, self.height, self.pixels)?;
+        match orientation {
+            2 | 5 | 7 => imageops::flip_horizontal_in_place(&mut image),
+            4 => imageops::flip_vertical_in_place(&mut image),
+            _ => (),
+        }
+        let image = match orientation {
+            3 => imageops::rotate180(&image),
+            6 | 7 => imageops::rotate90(&image),
+            5 | 8 => imageops::rotate270(&image),
+            _ => image,
+        };
+        Some(Frame {
+            width: image.width(),
+            height: image.height(),
+            pixels: image.into_raw(),
+            delay: self.delay,
+        })
+    }
+}
+
+/// Image contains every decoded frame of an image, still images have exactly one frame
+pub struct Image {
+    /// frames in display order
+    pub frames: Vec<Frame>,
+    /// plays is how many times the animation is played before stopping on its last frame,
+    /// None when it repeats forever
+    pub plays: Option<u32>,
+    /// pages is the number of pages in the file the image was decoded from, 1 for formats without
+    /// pages
+    pub pages: usize,
+    /// vector is the parsed document of a vector image, frames then holds a single raster of it
+    pub vector: Option<Svg>,
+    /// orientation is the EXIF orientation of the image, 1 when it is stored upright or has no
+    /// EXIF data
+    pub orientation: u32,
+    /// icc_profile is the ICC color profile embedded in the image, if any
+    pub icc_profile: Option<Vec<u8>>,
+    /// hdr holds the float pixels of high dynamic range and high bit depth images, frames then
+    /// holds them mapped at no exposure change and the default gamma
+    pub hdr: Option<Hdr>,
+}
+
+impl Image {
+    /// Still image of a single frame, upright with one page and no color profile
+    pub fn still(frame: Frame) -> Self {
+        Image::animated(vec![frame], Some(1))
+    }
+
+    /// Image of frames played plays times, None repeating forever, upright with one page and no
+    /// color profile
+    pub fn animated(frames: Vec<Frame>, plays: Option<u32>) -> Self {
+        Image {
+            frames,
+            plays,
+            pages: 1,
+            vector: None,
+            orientation: 1,
+            icc_profile: None,
+            hdr: None,
+        }
+    }
+
+    /// Whether the image has more than one frame to play
+    pub fn is_animated(&self) -> bool {
+        self.frames.len() > 1
+    }
+
+    /// Number of bytes the decoded pixels take up in memory
+    pub fn memory_size(&self) -> usize {
+        let frames: usize = self.frames.iter().map(|frame| frame.pixels.len()).sum();
+        frames + self.hdr.as_ref().map_or(0, Hdr::memory_size)
+    }
+}
+
+/// Decodes a page of the image at path, detecting the format from the contents of the file
+/// The page is ignored for formats without pages
+/// Images inside archives and the image piped to standard input are read from memory
+pub fn open(path: &Path, page: usize) -> Result<Image, String> {
+    decode(&read(path)?, page)
+}
+
+/// Decodes the first frame of the image at path into a thumbnail at most size pixels a side,
+/// turned upright from its EXIF orientation
+pub fn thumbnail(path: &Path, size: u32) -> Result<Frame, String> {
+    let image = open(path, 0)?;
+    let orientation = image.orientation;
+    let frame = match image.frames.into_iter().next() {
+        Some(frame) => frame,
+        None => return Err("Image has no frames".to_string()),
+    };
+    let scale = size as f32 / frame.width.max(frame.height) as f32;
+    let frame = if scale < 1.0 {
+        let width = ((frame.width as f32 * scale).round() as u32).max(1);
+        let height = ((frame.height as f32 * scale).round() as u32).max(1);
+        frame.resized(width, height)
+    } else {
+        Some(frame)
+    };
+    frame
+        .and_then(|frame| frame.oriented(orientation))
+        .ok_or_else(|| "Image pixels do not match its size".to_string())
+}
+
+/// Reads the bytes of the image at path, from inside its archive or from standard input for
+/// virtual paths
+pub fn read(path: &Path) -> Result<Vec<u8>, String> {
+    if archive::is_archived(path) {
+        archive::read(path)
+    } else if is_stdin(path) {
+        Ok(stdin::bytes())
+    } else {
+        std::fs::read(path).map_err(|e| e.to_string())
+    }
+}
+
+/// Decodes a page of an image held in memory, detecting the format from its contents
+pub fn decode(bytes: &[u8], page: usize) -> Result<Image, String> {
+    let mut image = decode_image(bytes, page)?;
+    image.orientation = exif_orientation(bytes);
+    image.icc_profile = icc_profile(bytes);
+    Ok(image)
+}
+
+/// Decodes the pixels of a page of an image by its format
+fn decode_image(bytes: &[u8], page: usize) -> Result<Image, String> {
+    let reader = Reader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?;
+    match reader.format() {
+        Some(ImageFormat::Gif) => {
+            let decoder = GifDecoder::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+            decode_frames(decoder.into_frames(), gif_plays(bytes))
+        }
+        Some(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+            if decoder.is_apng() {
+                decode_frames(decoder.apng().into_frames(), apng_plays(bytes))
+            } else {
+                decode_still(reader)
+            }
+        }
+        Some(ImageFormat::WebP) => {
+            let decoder = WebPDecoder::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+            if decoder.has_animation() {
+                decode_frames(decoder.into_frames(), webp_plays(bytes))
+            } else {
+                decode_still(reader)
+            }
+        }
+        Some(ImageFormat::Tiff) if is_raw(bytes) => raw::decode_preview(bytes),
+        Some(ImageFormat::Tiff) => tif::decode_page(bytes, page),
+        Some(ImageFormat::Ico) | None if is_icon(bytes) => ico::decode_size(bytes, page),
+        None if is_svg(bytes) => decode_svg(bytes),
+        None => {
+            // TGA is the only supported format without a signature to recognize it by
+            let mut reader = reader;
+            reader.set_format(ImageFormat::Tga);
+            decode_still(reader)
+        }
+        _ => decode_still(reader),
+    }
+}
+
+/// Reads the orientation from the EXIF data of JPEG, TIFF, PNG and WebP files, 1 when there is
+/// none
+fn exif_orientation(bytes: &[u8]) -> u32 {
+    exif::Reader::new()
+        .read_from_container(&mut Cursor::new(bytes))
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        })
+        .filter(|orientation| (1..=8).contains(orientation))
+        .unwrap_or(1)
+}
+
+/// Reads the ICC profile embedded in JPEG, PNG, WebP and TIFF files
+fn icc_profile(bytes: &[u8]) -> Option<Vec<u8>> {
+    let format = Reader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()?
+        .format()?;
+    let cursor = Cursor::new(bytes);
+    match format {
+        ImageFormat::Jpeg => JpegDecoder::new(cursor).ok()?.icc_profile(),
+        ImageFormat::Png => PngDecoder::new(cursor).ok()?.icc_profile(),
+        ImageFormat::WebP => WebPDecoder::new(cursor).ok()?.icc_profile(),
+        ImageFormat::Tiff => TiffDecoder::new(cursor).ok()?.icc_profile(),
+        _ => None,
+    }
+}
+
+/// Decodes an image with a single frame
+fn decode_still(reader: Reader<Cursor<&[u8]>>) -> Result<Image, String> {
+    let image = reader.decode().map_err(|e| e.to_string())?;
+    let hdr = Hdr::from_image(&image);
+    let pixels = match hdr.as_ref() {
+        Some(hdr) => hdr.tone_map(0.0, DEFAULT_GAMMA),
+        None => image.to_rgba8().into_raw(),
+    };
+    Ok(Image {
+        hdr,
+        ..Image::still(Frame {
+            width: image.width(),
+            height: image.height(),
+            pixels,
+            delay: DEFAULT_DELAY,
+        })
+    })
+}
+
+/// Parses an SVG and rasterizes it at its natural size
+fn decode_svg(bytes: &[u8]) -> Result<Image, String> {
+    let svg = Svg::parse(bytes)?;
+    let frame = svg.rasterize(1.0)?;
+    Ok(Image {
+        vector: Some(svg),
+        ..Image::still(frame)
+    })
+}
+
+/// Decodes every frame of an animation
+fn decode_frames(frames: Frames, plays: Option<u32>) -> Result<Image, String> {
+    let frames = frames.collect_frames().map_err(|e| e.to_string())?;
+    if frames.is_empty() {
+        return Err("animation has no frames".to_string());
+    }
+    let frames = frames
+        .into_iter()
+        .map(|frame| {
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let delay = frame_delay(numer, denom);
+            let buffer = frame.into_buffer();
+            Frame {
+                width: buffer.width(),
+                height: buffer.height(),
+                pixels: buffer.into_raw(),
+                delay,
+            }
+        })
+        .collect();
+    Ok(Image::animated(frames, plays))
+}
+
+/// Number of plays from the NETSCAPE2.0 looping extension of a gif
+/// Gifs without the extension play once, otherwise the loop count is the number of repeats after
+/// the first play with 0 repeating forever. Gifs that can't be read repeat forever
+fn gif_plays(bytes: &[u8]) -> Option<u32> {
+    // Header and logical screen descriptor
+    let flags = *bytes.get(10)?;
+    let mut i = 13;
+    if flags & 0x80 != 0 {
+        // Skip global color table
+        i += 3 << ((flags & 0x07) + 1);
+    }
+    loop {
+        match *bytes.get(i)? {
+            // Extension introducer
+            0x21 => {
+                let label = *bytes.get(i + 1)?;
+                i += 2;
+                let first_block = bytes.get(i..i + 1 + usize::from(*bytes.get(i)?))?;
+                if label == 0xFF
+                    && (first_block.get(1..) == Some(&b"NETSCAPE2.0"[..])
+                        || first_block.get(1..) == Some(&b"ANIMEXTS1.0"[..]))
+                {
+                    let loop_block = bytes.get(i + first_block.len()..)?;
+                    if loop_block.len() >= 4 && loop_block[0] >= 3 && loop_block[1] == 1 {
+                        let loops = u16::from_le_bytes([loop_block[2], loop_block[3]]);
+                        return match loops {
+                            0 => None,
+                            n => Some(u32::from(n) + 1),
+                        };
+                    }
+                }
+                i = skip_gif_sub_blocks(bytes, i)?;
+            }
+            // Image descriptor
+            0x2C => {
+                let flags = *bytes.get(i + 9)?;
+                i += 10;
+                if flags & 0x80 != 0 {
+                    // Skip local color table
+                    i += 3 << ((flags & 0x07) + 1);
+                }
+                // Skip LZW minimum code size then the image data
+                i = skip_gif_sub_blocks(bytes, i + 1)?;
+            }
+            // Trailer or anything unexpected, no looping extension was found
+            _ => return Some(1),
+        }
+    }
+}
+
+/// Returns the index after a chain of gif data sub-blocks starting at `i`
+fn skip_gif_sub_blocks(bytes: &[u8], mut i: usize) -> Option<usize> {
+    loop {
+        let len = usize::from(*bytes.get(i)?);
+        i += 1;
+        if len == 0 {
+            return Some(i);
+        }
+        i += len;
+    }
+}
+
+/// Number of plays from the acTL chunk of an animated png, 0 repeats forever
+fn apng_plays(bytes: &[u8]) -> Option<u32> {
+    // Skip png signature
+    let mut i = 8;
+    while let Some(header) = bytes.get(i..i + 8) {
+        let len = u32::from_be_bytes(header[0..4].try_into().ok()?) as usize;
+        if &header[4..8] == b"acTL" {
+            let data = bytes.get(i + 8..i + 16)?;
+            return match u32::from_be_bytes(data[4..8].try_into().ok()?) {
+                0 => None,
+                n => Some(n),
+            };
+        }
+        // Length, type, data and crc
+        i += 12 + len;
+    }
+    None
+}
+
+/// Number of plays from the ANIM chunk of an animated webp, 0 repeats forever
+fn webp_plays(bytes: &[u8]) -> Option<u32> {
+    // Skip RIFF header
+    let mut i = 12;
+    while let Some(header) = bytes.get(i..i + 8) {
+        let len = u32::from_le_bytes(header[4..8].try_into().ok()?) as usize;
+        if &header[0..4] == b"ANIM" {
+            let data = bytes.get(i + 8..i + 14)?;
+            return match u16::from_le_bytes([data[4], data[5]]) {
+                0 => None,
+                n => Some(u32::from(n)),
+            };
+        }
+        // Chunks are padded to an even length
+        i += 8 + len + len % 2;
+    }
+    None
+}
+
+/// Converts a delay given as a fraction of milliseconds to a Duration, replacing delays that are
+/// too short to be intended with the default
+fn frame_delay(numer: u32, denom: u32) -> Duration {
+    if denom == 0 {
+        return DEFAULT_DELAY;
+    }
+    let delay = Duration::from_micros(u64::from(numer) * 1000 / u64::from(denom));
+    if delay <= MIN_DELAY {
+        DEFAULT_DELAY
+    } else {
+        delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        apng_plays, decode, exif_orientation, frame_delay, gif_plays, webp_plays, Frame,
+        DEFAULT_DELAY,
+    };
+    use std::time::Duration;
+
+    /// Decodes a fixture and checks its size and the RGBA value of its first and last pixels
+    fn assert_fixture(bytes: &[u8], size: (u32, u32), first: [u8; 4], last: [u8; 4]) {
+        let image = decode(bytes, 0).unwrap();
+        let frame = &image.frames[0];
+        assert_eq!((frame.width, frame.height), size);
+        assert_eq!(frame.pixels[..4], first);
+        assert_eq!(frame.pixels[frame.pixels.len() - 4..], last);
+    }
+
+    /// A 1x1 gif with a two color global table, optionally with a looping extension
+    fn tiny_gif(loops: Option<u16>) -> Vec<u8> {
+        let mut gif = b"GIF89a\x01\x00\x01\x00\x80\x00\x00".to_vec();
+        gif.extend_from_slice(&[0, 0, 0, 255, 255, 255]);
+        if let Some(loops) = loops {
+            gif.extend_from_slice(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01");
+            gif.extend_from_slice(&loops.to_le_bytes());
+            gif.push(0);
+        }
+        gif.extend_from_slice(b"\x2C\x00\x00\x00\x00\x01\x00\x01\x00\x00\x02\x02\x44\x01\x00\x3B");
+        gif
+    }
+
+    #[test]
+    fn test_resized_frame_has_new_size() {
+        let image = decode(include_bytes!("../../tests/fixtures/rgba.qoi"), 0).unwrap();
+        let frame = image.frames[0].resized(1, 1).unwrap();
+        assert_eq!((frame.width, frame.height), (1, 1));
+        assert_eq!(frame.pixels.len(), 4);
+    }
+
+    #[test]
+    fn test_oriented_frame_is_upright() {
+        let (a, b) = ([1, 1, 1, 255], [2, 2, 2, 255]);
+        let frame = |orientation| {
+            Frame {
+                width: 2,
+                height: 1,
+                pixels: [a, b].concat(),
+                delay: DEFAULT_DELAY,
+            }
+            .oriented(orientation)
+            .unwrap()
+        };
+        let mirrored = frame(2);
+        assert_eq!((mirrored.width, mirrored.height), (2, 1));
+        assert_eq!(mirrored.pixels, [b, a].concat());
+        let right = frame(6);
+        assert_eq!((right.width, right.height), (1, 2));
+        assert_eq!(right.pixels, [a, b].concat());
+        assert_eq!(frame(8).pixels, [b, a].concat());
+        assert_eq!(frame(1).pixels, [a, b].concat());
+    }
+
+    #[test]
+    fn test_decodes_raster_fixtures() {
+        let red = [255, 0, 0, 255];
+        let white = [255, 255, 255, 255];
+        let black = [0, 0, 0, 255];
+        let fixtures: [(&[u8], [u8; 4], [u8; 4]); 5] = [
+            (include_bytes!("../../tests/fixtures/rgb.tga"), red, white),
+            (include_bytes!("../../tests/fixtures/rgb.ppm"), red, white),
+            (
+                include_bytes!("../../tests/fixtures/gray.pgm"),
+                black,
+                white,
+            ),
+            (
+                include_bytes!("../../tests/fixtures/bits.pbm"),
+                black,
+                black,
+            ),
+            (include_bytes!("../../tests/fixtures/rgba.qoi"), red, white),
+        ];
+        for (bytes, first, last) in fixtures.iter() {
+            assert_fixture(bytes, (2, 2), *first, *last);
+        }
+    }
+
+    #[test]
+    fn test_decodes_icon_sizes_as_pages() {
+        let image = decode(include_bytes!("../../tests/fixtures/sizes.ico"), 1).unwrap();
+        assert_eq!(image.pages, 2);
+        assert_eq!(image.frames[0].width, 1);
+        let cursor = include_bytes!("../../tests/fixtures/pointer.cur");
+        assert_fixture(cursor, (2, 2), [255, 0, 0, 255], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_decodes_still_gif_as_single_frame() {
+        let gif = include_bytes!("../../tests/fixtures/still.gif");
+        let image = decode(gif, 0).unwrap();
+        assert_eq!(image.frames.len(), 1);
+        assert!(!image.is_animated());
+        assert_fixture(gif, (1, 1), [255, 0, 0, 255], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_frame_delay_keeps_regular_delays() {
+        assert_eq!(frame_delay(40, 1), Duration::from_millis(40));
+    }
+
+    #[test]
+    fn test_frame_delay_replaces_zero_and_tiny_delays() {
+        assert_eq!(frame_delay(0, 1), DEFAULT_DELAY);
+        assert_eq!(frame_delay(10, 1), DEFAULT_DELAY);
+    }
+
+    #[test]
+    fn test_frame_delay_with_no_denominator_is_default() {
+        assert_eq!(frame_delay(50, 0), DEFAULT_DELAY);
+    }
+
+    #[test]
+    fn test_gif_plays_reads_looping_extension() {
+        assert_eq!(gif_plays(&tiny_gif(Some(0))), None);
+        assert_eq!(gif_plays(&tiny_gif(Some(2))), Some(3));
+    }
+
+    #[test]
+    fn test_gif_without_looping_extension_plays_once() {
+        assert_eq!(gif_plays(&tiny_gif(None)), Some(1));
+    }
+
+    #[test]
+    fn test_apng_plays_reads_actl_chunk() {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend_from_slice(&[0, 0, 0, 13]);
+        png.extend_from_slice(b"IHDR");
+        png.extend_from_slice(&[0; 13 + 4]);
+        png.extend_from_slice(&[0, 0, 0, 8]);
+        png.extend_from_slice(b"acTL");
+        png.extend_from_slice(&[0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 0]);
+        assert_eq!(apng_plays(&png), Some(3));
+        let len = png.len();
+        png[len - 5] = 0;
+        assert_eq!(apng_plays(&png), None);
+    }
+
+    #[test]
+    fn test_webp_plays_reads_anim_chunk() {
+        let mut webp = b"RIFF\x00\x00\x00\x00WEBP".to_vec();
+        webp.extend_from_slice(b"VP8X\x0A\x00\x00\x00");
+        webp.extend_from_slice(&[0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        webp.extend_from_slice(b"ANIM\x06\x00\x00\x00");
+        webp.extend_from_slice(&[0, 0, 0, 0, 4, 0]);
+        assert_eq!(webp_plays(&webp), Some(4));
+        let len = webp.len();
+        webp[len - 2] = 0;
+        assert_eq!(webp_plays(&webp), None);
+    }
+
+    #[test]
+    fn test_exif_orientation_reads_jpeg_app1() {
+        let mut jpeg = b"\xFF\xD8\xFF\xE1\x00\x22Exif\x00\x00".to_vec();
+        // Big endian TIFF header and a directory with only the orientation
+        jpeg.extend_from_slice(b"MM\x00\x2A\x00\x00\x00\x08\x00\x01");
+        jpeg.extend_from_slice(&[0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0]);
+        jpeg.extend_from_slice(&[0, 0, 0, 0]);
+        jpeg.extend_from_slice(b"\xFF\xD9");
+        assert_eq!(exif_orientation(&jpeg), 6);
+        assert_eq!(exif_orientation(b"\xFF\xD8\xFF\xD9"), 1);
+    }
+}
@@ -0,0 +1,332 @@
+//! Previews of camera RAW files (CR2, NEF, ARW and DNG). Instead of demosaicing the sensor data
+//! the largest JPEG preview the camera embedded in the TIFF structure of the file is decoded
+
+use super::{Frame, Image, DEFAULT_DELAY};
+use image::ImageFormat;
+use std::collections::HashSet;
+
+/// Tags of TIFF image file directories used to find previews
+const TAG_COMPRESSION: u16 = 0x0103;
+const TAG_MAKE: u16 = 0x010F;
+const TAG_STRIP_OFFSETS: u16 = 0x0111;
+const TAG_STRIP_BYTE_COUNTS: u16 = 0x0117;
+const TAG_SUB_IFDS: u16 = 0x014A;
+const TAG_JPEG_OFFSET: u16 = 0x0201;
+const TAG_JPEG_LENGTH: u16 = 0x0202;
+const TAG_DNG_VERSION: u16 = 0xC612;
+/// Compression values of JPEG compressed strips, old style and new style
+const JPEG_COMPRESSIONS: [u32; 2] = [6, 7];
+/// Stops walking files with corrupt or looping directory offsets
+const MAX_IFDS: usize = 64;
+
+/// Whether a file with a TIFF header is a camera RAW file rather than an ordinary TIFF
+pub fn is_raw(bytes: &[u8]) -> bool {
+    // CR2 marks itself right after the TIFF header
+    if bytes.get(8..10) == Some(b"CR") {
+        return true;
+    }
+    let tiff = match Tiff::parse(bytes) {
+        Some(tiff) => tiff,
+        None => return false,
+    };
+    let ifd = match tiff.first_ifd().and_then(|at| tiff.ifd(at)) {
+        Some(ifd) => ifd,
+        None => return false,
+    };
+    if ifd.entries.iter().any(|e| e.tag == TAG_DNG_VERSION) {
+        return true;
+    }
+    match ifd.find(TAG_MAKE) {
+        Some(make) => {
+            let make = tiff.ascii(make).to_uppercase();
+            make.starts_with("NIKON") || make.starts_with("SONY")
+        }
+        None => false,
+    }
+}
+
+/// Decodes the largest embedded JPEG preview of a RAW file
+pub fn decode_preview(bytes: &[u8]) -> Result<Image, String> {
+    let tiff = Tiff::parse(bytes).ok_or_else(|| "RAW file has no TIFF header".to_string())?;
+    let preview = previews(&tiff)
+        .into_iter()
+        .filter_map(|jpeg| jpeg_size(jpeg).map(|(w, h)| (u64::from(w) * u64::from(h), jpeg)))
+        .max_by_key(|&(area, _)| area)
+        .map(|(_, jpeg)| jpeg)
+        .ok_or_else(|| "RAW file has no embedded JPEG preview".to_string())?;
+    let image = image::load_from_memory_with_format(preview, ImageFormat::Jpeg)
+        .map_err(|e| e.to_string())?
+        .to_rgba8();
+    Ok(Image::still(Frame {
+        width: image.width(),
+        height: image.height(),
+        pixels: image.into_raw(),
+        delay: DEFAULT_DELAY,
+    }))
+}
+
+/// Every embedded JPEG stream found walking the image file directories and their sub directories
+fn previews<'a>(tiff: &Tiff<'a>) -> Vec<&'a [u8]> {
+    let mut previews = Vec::new();
+    let mut pending: Vec<usize> = tiff.first_ifd().into_iter().collect();
+    let mut visited = HashSet::new();
+    while let Some(at) = pending.pop() {
+        if visited.len() >= MAX_IFDS || !visited.insert(at) {
+            continue;
+        }
+        let ifd = match tiff.ifd(at) {
+            Some(ifd) => ifd,
+            None => continue,
+        };
+        if ifd.next != 0 {
+            pending.push(ifd.next);
+        }
+        if let Some(sub_ifds) = ifd.find(TAG_SUB_IFDS) {
+            pending.extend(tiff.values(sub_ifds).into_iter().map(|v| v as usize));
+        }
+        let value = |tag| {
+            ifd.find(tag)
+                .and_then(|entry| tiff.values(entry).first().copied())
+        };
+        if let (Some(offset), Some(length)) = (value(TAG_JPEG_OFFSET), value(TAG_JPEG_LENGTH)) {
+            previews.extend(tiff.slice(offset, length));
+        }
+        // Single strip JPEG compressed images are previews too, unless they are lossless raw data
+        let compression = value(TAG_COMPRESSION).unwrap_or(1);
+        if JPEG_COMPRESSIONS.contains(&compression) {
+            let offsets = ifd.find(TAG_STRIP_OFFSETS).map(|e| tiff.values(e));
+            let lengths = ifd.find(TAG_STRIP_BYTE_COUNTS).map(|e| tiff.values(e));
+            if let (Some(offsets), Some(lengths)) = (offsets, lengths) {
+                if offsets.len() == 1 && lengths.len() == 1 {
+                    previews.extend(tiff.slice(offsets[0], lengths[0]));
+                }
+            }
+        }
+    }
+    previews
+}
+
+/// Width and height of a baseline or progressive JPEG, None for anything else including the
+/// lossless JPEG many cameras store their sensor data as
+fn jpeg_size(jpeg: &[u8]) -> Option<(u16, u16)> {
+    if !jpeg.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let be16 = |at: usize| {
+        jpeg.get(at..at + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+    };
+    let mut at = 2;
+    loop {
+        if *jpeg.get(at)? != 0xFF {
+            return None;
+        }
+        let marker = *jpeg.get(at + 1)?;
+        match marker {
+            // Fill bytes before a marker
+            0xFF => at += 1,
+            // Markers without a length
+            0x01 | 0xD0..=0xD7 => at += 2,
+            0xC0..=0xC2 => return Some((be16(at + 7)?, be16(at + 5)?)),
+            // Lossless, hierarchical and arithmetic coded frames, or the end of the headers
+            0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF | 0xD9 | 0xDA => return None,
+            _ => at += 2 + usize::from(be16(at + 2)?),
+        }
+    }
+}
+
+/// Tiff reads the header and image file directories of a TIFF structured file
+struct Tiff<'a> {
+    bytes: &'a [u8],
+    little_endian: bool,
+}
+
+/// Ifd is an image file directory, a list of tagged entries describing one image
+struct Ifd {
+    entries: Vec<Entry>,
+    /// offset of the next directory, 0 when this is the last one
+    next: usize,
+}
+
+/// Entry is a single tag of an image file directory
+struct Entry {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    /// offset of the 4 bytes holding either the value or the offset of the value
+    at: usize,
+}
+
+impl Ifd {
+    fn find(&self, tag: u16) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.tag == tag)
+    }
+}
+
+impl<'a> Tiff<'a> {
+    fn parse(bytes: &'a [u8]) -> Option<Self> {
+        let little_endian = match bytes.get(0..4)? {
+            b"II\x2A\x00" => true,
+            b"MM\x00\x2A" => false,
+            _ => return None,
+        };
+        Some(Tiff {
+            bytes,
+            little_endian,
+        })
+    }
+
+    fn u16(&self, at: usize) -> Option<u16> {
+        let b = self.bytes.get(at..at + 2)?;
+        Some(if self.little_endian {
+            u16::from_le_bytes([b[0], b[1]])
+        } else {
+            u16::from_be_bytes([b[0], b[1]])
+        })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let b = self.bytes.get(at..at + 4)?;
+        Some(if self.little_endian {
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        } else {
+            u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+        })
+    }
+
+    fn first_ifd(&self) -> Option<usize> {
+        self.u32(4).map(|at| at as usize)
+    }
+
+    fn ifd(&self, at: usize) -> Option<Ifd> {
+        let count = usize::from(self.u16(at)?);
+        let entries = (0..count)
+            .map(|i| {
+                let entry = at + 2 + i * 12;
+                Some(Entry {
+                    tag: self.u16(entry)?,
+                    kind: self.u16(entry + 2)?,
+                    count: self.u32(entry + 4)?,
+                    at: entry + 8,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let next = self.u32(at + 2 + count * 12).unwrap_or(0) as usize;
+        Some(Ifd { entries, next })
+    }
+
+    /// Offset of the value of an entry, values of 4 bytes or less are stored in the entry itself
+    fn value_offset(&self, entry: &Entry, size: usize) -> Option<usize> {
+        if size * entry.count as usize <= 4 {
+            Some(entry.at)
+        } else {
+            self.u32(entry.at).map(|at| at as usize)
+        }
+    }
+
+    /// Integer values of a SHORT, LONG or IFD entry, empty for other kinds of entry
+    fn values(&self, entry: &Entry) -> Vec<u32> {
+        let size = match entry.kind {
+            3 => 2,
+            4 | 13 => 4,
+            _ => return Vec::new(),
+        };
+        let at = match self.value_offset(entry, size) {
+            Some(at) => at,
+            None => return Vec::new(),
+        };
+        (0..entry.count as usize)
+            .map_while(|i| match size {
+                2 => self.u16(at + i * 2).map(u32::from),
+                _ => self.u32(at + i * 4),
+            })
+            .collect()
+    }
+
+    /// Text of an ASCII entry
+    fn ascii(&self, entry: &Entry) -> String {
+        let text = self
+            .value_offset(entry, 1)
+            .and_then(|at| self.bytes.get(at..at + entry.count as usize))
+            .unwrap_or(&[]);
+        String::from_utf8_lossy(text)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+
+    fn slice(&self, offset: u32, length: u32) -> Option<&'a [u8]> {
+        let start = offset as usize;
+        self.bytes.get(start..start.checked_add(length as usize)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_preview, is_raw, jpeg_size};
+    use image::codecs::jpeg::JpegEncoder;
+    use image::ColorType;
+
+    fn jpeg(width: u16, height: u16) -> Vec<u8> {
+        let mut jpeg = Vec::new();
+        let pixels = vec![128; usize::from(width) * usize::from(height) * 3];
+        JpegEncoder::new(&mut jpeg)
+            .encode(&pixels, width.into(), height.into(), ColorType::Rgb8)
+            .unwrap();
+        jpeg
+    }
+
+    /// A little endian NEF-like file, IFD0 holds a Make and a small JPEG, its sub IFD a bigger one
+    fn nef(small: &[u8], big: &[u8]) -> Vec<u8> {
+        let entry = |tag: u16, kind: u16, count: u32, value: u32| {
+            let mut e = tag.to_le_bytes().to_vec();
+            e.extend_from_slice(&kind.to_le_bytes());
+            e.extend_from_slice(&count.to_le_bytes());
+            e.extend_from_slice(&value.to_le_bytes());
+            e
+        };
+        let ifd0 = 8;
+        let sub_ifd = ifd0 + 2 + 4 * 12 + 4;
+        let small_at = sub_ifd + 2 + 2 * 12 + 4;
+        let big_at = small_at + small.len();
+        let make_at = big_at + big.len();
+        let mut raw = b"II\x2A\x00".to_vec();
+        raw.extend_from_slice(&(ifd0 as u32).to_le_bytes());
+        raw.extend_from_slice(&4u16.to_le_bytes());
+        raw.extend(entry(0x010F, 2, 6, make_at as u32));
+        raw.extend(entry(0x014A, 4, 1, sub_ifd as u32));
+        raw.extend(entry(0x0201, 4, 1, small_at as u32));
+        raw.extend(entry(0x0202, 4, 1, small.len() as u32));
+        raw.extend_from_slice(&0u32.to_le_bytes());
+        raw.extend_from_slice(&2u16.to_le_bytes());
+        raw.extend(entry(0x0201, 4, 1, big_at as u32));
+        raw.extend(entry(0x0202, 4, 1, big.len() as u32));
+        raw.extend_from_slice(&0u32.to_le_bytes());
+        raw.extend_from_slice(small);
+        raw.extend_from_slice(big);
+        raw.extend_from_slice(b"NIKON\0");
+        raw
+    }
+
+    #[test]
+    fn test_decode_preview_picks_largest_jpeg() {
+        let raw = nef(&jpeg(2, 2), &jpeg(8, 4));
+        assert!(is_raw(&raw));
+        let image = decode_preview(&raw).unwrap();
+        assert_eq!((image.frames[0].width, image.frames[0].height), (8, 4));
+    }
+
+    #[test]
+    fn test_is_raw_rejects_plain_tiff() {
+        let mut tiff = b"II\x2A\x00\x08\x00\x00\x00".to_vec();
+        tiff.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        assert!(!is_raw(&tiff));
+        assert!(is_raw(b"II\x2A\x00\x10\x00\x00\x00CR\x02\x00"));
+    }
+
+    #[test]
+    fn test_jpeg_size_skips_lossless_jpeg() {
+        assert_eq!(jpeg_size(&jpeg(3, 5)), Some((3, 5)));
+        let lossless = [0xFF, 0xD8, 0xFF, 0xC3, 0x00, 0x0B, 8, 0, 5, 0, 3];
+        assert_eq!(jpeg_size(&lossless), None);
+    }
+}
@@ -0,0 +1,121 @@
+//! Rasterization of SVG images, the parsed document is kept so it can be rasterized again at any
+//! scale without reading the file a second time
+
+use super::{Frame, DEFAULT_DELAY};
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{fontdb, Options, Tree};
+use std::sync::Arc;
+
+/// Largest width or height in pixels an SVG is rasterized at, larger scales are upscaled bitmaps
+const MAX_RASTER_SIZE: f32 = 8192.0;
+
+lazy_static! {
+    /// System fonts used for text in SVGs, loaded once as loading them is slow
+    static ref FONTS: Arc<fontdb::Database> = {
+        let mut fonts = fontdb::Database::new();
+        fonts.load_system_fonts();
+        Arc::new(fonts)
+    };
+}
+
+/// Svg is a parsed SVG document
+pub struct Svg {
+    tree: Tree,
+}
+
+impl Svg {
+    /// Parses an SVG or gzip compressed SVG document
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let options = Options {
+            fontdb: FONTS.clone(),
+            ..Default::default()
+        };
+        let tree = Tree::from_data(bytes, &options).map_err(|e| e.to_string())?;
+        Ok(Svg { tree })
+    }
+
+    /// Natural width and height of the document
+    pub fn size(&self) -> (f32, f32) {
+        let size = self.tree.size();
+        (size.width(), size.height())
+    }
+
+    /// The scale the document is actually rasterized at when asked for `scale`, capped so the
+    /// raster stays within MAX_RASTER_SIZE
+    pub fn raster_scale(&self, scale: f32) -> f32 {
+        let (width, height) = self.size();
+        scale.min(MAX_RASTER_SIZE / width.max(height))
+    }
+
+    /// Rasterizes the document at `scale` times its natural size, see `raster_scale`
+    pub fn rasterize(&self, scale: f32) -> Result<Frame, String> {
+        let scale = self.raster_scale(scale);
+        let (width, height) = self.size();
+        let width = ((width * scale).ceil() as u32).max(1);
+        let height = ((height * scale).ceil() as u32).max(1);
+        let mut pixmap = Pixmap::new(width, height)
+            .ok_or_else(|| format!("can't rasterize SVG at {}x{}", width, height))?;
+        resvg::render(
+            &self.tree,
+            Transform::from_scale(scale, scale),
+            &mut pixmap.as_mut(),
+        );
+        // Pixmaps are premultiplied by alpha, textures are not
+        let pixels = pixmap
+            .pixels()
+            .iter()
+            .flat_map(|pixel| {
+                let color = pixel.demultiply();
+                vec![color.red(), color.green(), color.blue(), color.alpha()]
+            })
+            .collect();
+        Ok(Frame {
+            width,
+            height,
+            pixels,
+            delay: DEFAULT_DELAY,
+        })
+    }
+}
+
+/// Whether the bytes look like an SVG document, either plain or gzip compressed
+pub fn is_svg(bytes: &[u8]) -> bool {
+    if bytes.starts_with(&[0x1F, 0x8B]) {
+        return true;
+    }
+    // The root element has to come after any xml declaration, comments or doctype
+    let head = &bytes[..bytes.len().min(4096)];
+    String::from_utf8_lossy(head).contains("<svg")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_svg, Svg};
+
+    const SQUARE: &[u8] = br##"<svg xmlns="http://www.w3.org/2000/svg" width="4" height="2">
+        <rect width="4" height="2" fill="#ff0000"/>
+    </svg>"##;
+
+    #[test]
+    fn test_is_svg_detects_documents() {
+        assert!(is_svg(SQUARE));
+        assert!(is_svg(b"<?xml version=\"1.0\"?>\n<svg></svg>"));
+        assert!(!is_svg(b"\x89PNG\r\n\x1a\n"));
+    }
+
+    #[test]
+    fn test_rasterize_scales_natural_size() {
+        let svg = Svg::parse(SQUARE).unwrap();
+        assert_eq!(svg.size(), (4.0, 2.0));
+        let frame = svg.rasterize(2.5).unwrap();
+        assert_eq!((frame.width, frame.height), (10, 5));
+        assert_eq!(&frame.pixels[0..4], &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_raster_scale_is_capped() {
+        let svg = Svg::parse(SQUARE).unwrap();
+        assert_eq!(svg.raster_scale(1.0), 1.0);
+        assert_eq!(svg.raster_scale(10_000.0), 2048.0);
+    }
+}
@@ -0,0 +1,219 @@
+//! Decoding of TIFF files page by page, each image file directory of a TIFF is a page
+
+use super::{Frame, Image, DEFAULT_DELAY};
+use image::{DynamicImage, ImageBuffer};
+use std::io::Cursor;
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::Tag;
+use tiff::ColorType;
+
+/// Decodes a single page of a TIFF, pages past the last page are an error
+pub fn decode_page(bytes: &[u8], page: usize) -> Result<Image, String> {
+    let mut decoder = Decoder::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+    let pages = count_pages(&mut decoder)?;
+    if page >= pages {
+        return Err(format!("page {} is past the last page {}", page + 1, pages));
+    }
+    decoder.seek_to_image(page).map_err(|e| e.to_string())?;
+    let (width, height) = decoder.dimensions().map_err(|e| e.to_string())?;
+    let color_type = decoder.colortype().map_err(|e| e.to_string())?;
+    let palette = match color_type {
+        ColorType::Palette(_) => decoder
+            .find_tag_unsigned_vec::<u16>(Tag::ColorMap)
+            .map_err(|e| e.to_string())?,
+        _ => None,
+    };
+    let data = decoder.read_image().map_err(|e| e.to_string())?;
+    let pixels = to_rgba8(width, height, color_type, data, palette)?;
+    Ok(Image {
+        pages,
+        ..Image::still(Frame {
+            width,
+            height,
+            pixels,
+            delay: DEFAULT_DELAY,
+        })
+    })
+}
+
+/// Counts the pages by walking every image file directory
+fn count_pages(decoder: &mut Decoder<Cursor<&[u8]>>) -> Result<usize, String> {
+    let mut pages = 1;
+    while decoder.more_images() {
+        decoder.next_image().map_err(|e| e.to_string())?;
+        pages += 1;
+    }
+    Ok(pages)
+}
+
+/// Converts the decoded samples of a page to RGBA with 8 bits per channel
+fn to_rgba8(
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    data: DecodingResult,
+    palette: Option<Vec<u16>>,
+) -> Result<Vec<u8>, String> {
+    let image = match (color_type, data) {
+        (ColorType::Gray(1), DecodingResult::U8(data)) => {
+            DynamicImage::ImageLuma8(buffer(width, height, expand_bits(width, height, &data))?)
+        }
+        (ColorType::Gray(8), DecodingResult::U8(data)) => {
+            DynamicImage::ImageLuma8(buffer(width, height, data)?)
+        }
+        (ColorType::Gray(16), DecodingResult::U16(data)) => {
+            DynamicImage::ImageLuma16(buffer(width, height, data)?)
+        }
+        (ColorType::GrayA(8), DecodingResult::U8(data)) => {
+            DynamicImage::ImageLumaA8(buffer(width, height, data)?)
+        }
+        (ColorType::GrayA(16), DecodingResult::U16(data)) => {
+            DynamicImage::ImageLumaA16(buffer(width, height, data)?)
+        }
+        (ColorType::RGB(8), DecodingResult::U8(data)) => {
+            DynamicImage::ImageRgb8(buffer(width, height, data)?)
+        }
+        (ColorType::RGB(16), DecodingResult::U16(data)) => {
+            DynamicImage::ImageRgb16(buffer(width, height, data)?)
+        }
+        (ColorType::RGBA(8), DecodingResult::U8(data)) => {
+            DynamicImage::ImageRgba8(buffer(width, height, data)?)
+        }
+        (ColorType::RGBA(16), DecodingResult::U16(data)) => {
+            DynamicImage::ImageRgba16(buffer(width, height, data)?)
+        }
+        (ColorType::CMYK(8), DecodingResult::U8(data)) => {
+            let rgb = data
+                .chunks_exact(4)
+                .flat_map(|cmyk| {
+                    let k = 255 - u16::from(cmyk[3]);
+                    let channel = |c: u8| ((255 - u16::from(c)) * k / 255) as u8;
+                    vec![channel(cmyk[0]), channel(cmyk[1]), channel(cmyk[2])]
+                })
+                .collect();
+            DynamicImage::ImageRgb8(buffer(width, height, rgb)?)
+        }
+        (ColorType::Palette(8), DecodingResult::U8(data)) => {
+            let palette = palette.ok_or_else(|| "palette TIFF has no color map".to_string())?;
+            if palette.len() < 3 * 256 {
+                return Err("palette TIFF color map is too short".to_string());
+            }
+            let rgb = data
+                .iter()
+                .flat_map(|&i| {
+                    let i = usize::from(i);
+                    // Color map holds every red, then every green, then every blue
+                    vec![
+                        (palette[i] >> 8) as u8,
+                        (palette[256 + i] >> 8) as u8,
+                        (palette[512 + i] >> 8) as u8,
+                    ]
+                })
+                .collect();
+            DynamicImage::ImageRgb8(buffer(width, height, rgb)?)
+        }
+        (color_type, _) => return Err(format!("unsupported TIFF color type {:?}", color_type)),
+    };
+    Ok(image.to_rgba8().into_raw())
+}
+
+/// Wraps samples in an image buffer, failing if there are too few samples for the dimensions
+fn buffer<P>(
+    width: u32,
+    height: u32,
+    data: Vec<P::Subpixel>,
+) -> Result<ImageBuffer<P, Vec<P::Subpixel>>, String>
+where
+    P: image::Pixel,
+{
+    ImageBuffer::from_raw(width, height, data)
+        .ok_or_else(|| "TIFF page has fewer samples than its dimensions".to_string())
+}
+
+/// Expands rows of 1 bit samples, each row padded to a whole byte, into 8 bit samples
+fn expand_bits(width: u32, height: u32, data: &[u8]) -> Vec<u8> {
+    let stride = (width as usize).div_ceil(8);
+    let mut samples = Vec::with_capacity(width as usize * height as usize);
+    for row in data.chunks(stride).take(height as usize) {
+        for x in 0..width as usize {
+            let bit = row.get(x / 8).map_or(0, |byte| (byte >> (7 - x % 8)) & 1);
+            samples.push(bit * 255);
+        }
+    }
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_page, expand_bits};
+
+    /// A little endian TIFF with one 1x1 8 bit gray page per value in `values`
+    fn gray_tiff(values: &[u8]) -> Vec<u8> {
+        let mut tiff = b"II\x2A\x00".to_vec();
+        let entry = |tag: u16, kind: u16, value: u32| {
+            let mut e = tag.to_le_bytes().to_vec();
+            e.extend_from_slice(&kind.to_le_bytes());
+            e.extend_from_slice(&1u32.to_le_bytes());
+            e.extend_from_slice(&value.to_le_bytes());
+            e
+        };
+        // Each page is the pixel, padding and then a directory of 8 entries
+        let page_len = 2 + 2 + 8 * 12 + 4;
+        tiff.extend_from_slice(&10u32.to_le_bytes());
+        for (i, value) in values.iter().enumerate() {
+            let pixel = 8 + i * page_len;
+            tiff.extend_from_slice(&[*value, 0]);
+            tiff.extend_from_slice(&8u16.to_le_bytes());
+            tiff.extend(entry(256, 3, 1));
+            tiff.extend(entry(257, 3, 1));
+            tiff.extend(entry(258, 3, 8));
+            tiff.extend(entry(259, 3, 1));
+            tiff.extend(entry(262, 3, 1));
+            tiff.extend(entry(273, 4, pixel as u32));
+            tiff.extend(entry(278, 3, 1));
+            tiff.extend(entry(279, 4, 1));
+            let next = if i + 1 < values.len() {
+                pixel + page_len + 2
+            } else {
+                0
+            };
+            tiff.extend_from_slice(&(next as u32).to_le_bytes());
+        }
+        tiff
+    }
+
+    #[test]
+    fn test_decode_page_reads_requested_page_and_page_count() {
+        let tiff = gray_tiff(&[10, 20, 30]);
+        let image = decode_page(&tiff, 1).unwrap();
+        assert_eq!(image.pages, 3);
+        assert_eq!(image.frames[0].pixels, vec![20, 20, 20, 255]);
+    }
+
+    #[test]
+    fn test_decode_page_past_last_page_is_error() {
+        let tiff = gray_tiff(&[10, 20]);
+        assert!(decode_page(&tiff, 2).is_err());
+    }
+
+    #[test]
+    fn test_decode_page_of_group_4_fax() {
+        let fax = include_bytes!("../../tests/fixtures/fax.tif");
+        let image = decode_page(fax, 0).unwrap();
+        assert_eq!(image.pages, 2);
+        // The left half of the first page is black
+        let pixels = &image.frames[0].pixels;
+        assert_eq!(pixels[..4], [0, 0, 0, 255]);
+        assert_eq!(pixels[15 * 4..16 * 4], [255, 255, 255, 255]);
+        // The second page is black below its top row
+        let pixels = &decode_page(fax, 1).unwrap().frames[0].pixels;
+        assert_eq!(pixels[..4], [255, 255, 255, 255]);
+        assert_eq!(pixels[16 * 4..17 * 4], [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_expand_bits_honours_row_padding() {
+        let samples = expand_bits(3, 2, &[0b1010_0000, 0b0100_0000]);
+        assert_eq!(samples, vec![255, 0, 255, 0, 255, 0]);
+    }
+}
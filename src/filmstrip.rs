@@ -0,0 +1,106 @@
+//! # Filmstrip
+//!
+//! Lays out a strip of thumbnails of the images before and after the current one along the bottom
+//! of the window. The current image keeps the middle of the strip, so the neighbours slide past it
+//! while stepping through the images.
+
+use sdl2::rect::Rect;
+
+/// Largest side of a thumbnail in the filmstrip in pixels
+const SIZE: u32 = 96;
+/// Space between the thumbnails and around the strip in pixels
+const GAP: u32 = 8;
+/// Most images shown on either side of the current image
+const MAX_NEIGHBOURS: usize = 7;
+
+/// Filmstrip is the layout of the strip of thumbnails that fits the window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Filmstrip {
+    /// number of images shown on either side of the current image
+    neighbours: usize,
+    /// width of the window
+    width: u32,
+    /// top edge of the strip
+    top: i32,
+}
+
+impl Filmstrip {
+    /// Filmstrip across a window width pixels wide, with its bottom edge at bottom
+    pub fn new(width: u32, bottom: u32) -> Self {
+        let pitch = SIZE + GAP;
+        let slots = (width.saturating_sub(GAP) / pitch).max(1) as usize;
+        Filmstrip {
+            neighbours: ((slots - 1) / 2).min(MAX_NEIGHBOURS),
+            width,
+            top: bottom as i32 - (SIZE + GAP * 2) as i32,
+        }
+    }
+
+    /// Band the strip is drawn on
+    pub fn area(&self) -> Rect {
+        Rect::new(0, self.top, self.width, SIZE + GAP * 2)
+    }
+
+    /// Images shown around index out of len images, along with the cells their thumbnails are
+    /// drawn in
+    pub fn cells(&self, index: usize, len: usize) -> Vec<(usize, Rect)> {
+        let pitch = (SIZE + GAP) as i32;
+        let middle = (self.width / 2) as i32;
+        let first = index.saturating_sub(self.neighbours);
+        let last = index
+            .saturating_add(self.neighbours)
+            .min(len.saturating_sub(1));
+        (first..=last)
+            .filter(|&i| i < len)
+            .map(|i| {
+                let offset = i as i32 - index as i32;
+                let center_x = middle + offset * pitch;
+                let cell = Rect::new(
+                    center_x - (SIZE / 2) as i32,
+                    self.top + GAP as i32,
+                    SIZE,
+                    SIZE,
+                );
+                (i, cell)
+            })
+            .collect()
+    }
+
+    /// Image whose thumbnail is at x, y with index the current image out of len images
+    pub fn hit(&self, index: usize, len: usize, x: i32, y: i32) -> Option<usize> {
+        self.cells(index, len)
+            .into_iter()
+            .find(|(_, cell)| cell.contains_point((x, y)))
+            .map(|(i, _)| i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Filmstrip, GAP, SIZE};
+
+    #[test]
+    fn test_filmstrip_centers_current_image() {
+        let strip = Filmstrip::new(5 * (SIZE + GAP) + GAP, 600);
+        assert_eq!(strip.area().bottom(), 600);
+        let cells = strip.cells(10, 20);
+        assert_eq!(
+            cells.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+            vec![8, 9, 10, 11, 12]
+        );
+        assert_eq!(cells[2].1.center().x(), strip.area().center().x());
+        // Missing neighbours at the ends leave their slots empty
+        let cells = strip.cells(0, 2);
+        assert_eq!(cells.len(), 2);
+        assert_eq!(cells[0].1.center().x(), strip.area().center().x());
+    }
+
+    #[test]
+    fn test_filmstrip_hit() {
+        let strip = Filmstrip::new(5 * (SIZE + GAP) + GAP, 600);
+        let (_, cell) = strip.cells(10, 20)[3];
+        assert_eq!(strip.hit(10, 20, cell.x() + 1, cell.y() + 1), Some(11));
+        assert_eq!(strip.hit(10, 20, cell.x() + 1, 0), None);
+        assert_eq!(strip.hit(0, 1, cell.x() + 1, cell.y() + 1), None);
+    }
+}
@@ -0,0 +1,349 @@
+//! # Flipbook
+//!
+//! Plays the images as the frames of a movie at a fixed frame rate, for reviewing numbered image
+//! sequences such as render outputs. Playback stays within an in and out point and either stops at
+//! the out point, loops back to the in point or bounces back and forth between them. Frames missing
+//! from the numbering and frames that fail to load are reported rather than skipped quietly.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Frame rates playback is offered at
+pub const FRAME_RATES: [u32; 3] = [24, 30, 60];
+/// How far ahead of playback frames are decoded
+const BUFFER_AHEAD: Duration = Duration::from_millis(500);
+
+/// Repeat is what playback does once it reaches the out point
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Repeat {
+    /// Stops at the out point
+    Once,
+    /// Starts over at the in point
+    Loop,
+    /// Plays backwards to the in point and forwards again
+    PingPong,
+}
+
+/// Flipbook tracks the timing, direction and range of flipbook playback
+#[derive(Clone, Debug)]
+pub struct Flipbook {
+    /// frames shown per second
+    pub fps: u32,
+    /// what happens at the out point
+    pub repeat: Repeat,
+    /// index of the first frame played
+    pub first: usize,
+    /// index of the last frame played
+    pub last: usize,
+    /// whether playback moves towards the out point
+    forward: bool,
+    /// when the next frame is due, None while paused
+    next_frame: Option<Instant>,
+    /// frame numbers absent from the numbering of the sequence
+    missing: Vec<u64>,
+    /// frame numbers that failed to load
+    failed: BTreeSet<u64>,
+}
+
+impl Flipbook {
+    /// Parses the frame rate, one of "once", "loop" or "pingpong" and a range of image numbers
+    /// such as "10-200" in any order, for a list of len images. Plays all images in a loop at 24
+    /// frames per second by default
+    pub fn parse(arguments: &str, len: usize) -> Result<Self, String> {
+        if len == 0 {
+            return Err("no images to play".to_string());
+        }
+        let mut flipbook = Flipbook {
+            fps: FRAME_RATES[0],
+            repeat: Repeat::Loop,
+            first: 0,
+            last: len - 1,
+            forward: true,
+            next_frame: Some(Instant::now()),
+            missing: Vec::new(),
+            failed: BTreeSet::new(),
+        };
+        for argument in arguments.split_whitespace() {
+            match argument {
+                "once" => flipbook.repeat = Repeat::Once,
+                "loop" => flipbook.repeat = Repeat::Loop,
+                "pingpong" => flipbook.repeat = Repeat::PingPong,
+                _ if argument.contains('-') => {
+                    let (first, last) = parse_range(argument, len)?;
+                    flipbook.first = first;
+                    flipbook.last = last;
+                }
+                _ => match argument.parse::<u32>() {
+                    Ok(fps) if FRAME_RATES.contains(&fps) => flipbook.fps = fps,
+                    _ => {
+                        return Err(format!(
+                            "\"{}\" is not a frame rate of 24, 30 or 60, a range or once, loop or pingpong",
+                            argument
+                        ))
+                    }
+                },
+            }
+        }
+        Ok(flipbook)
+    }
+
+    /// Time each frame is shown for
+    pub fn frame_time(&self) -> Duration {
+        Duration::from_secs(1) / self.fps
+    }
+
+    /// Whether the next frame is due at now
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.next_frame.is_some_and(|next| now >= next)
+    }
+
+    /// Time left until the next frame is due, None while paused
+    pub fn until_due(&self, now: Instant) -> Option<Duration> {
+        self.next_frame
+            .map(|next| next.saturating_duration_since(now))
+    }
+
+    /// Whether playback is paused
+    pub fn is_paused(&self) -> bool {
+        self.next_frame.is_none()
+    }
+
+    /// Pauses playback or resumes it with the next frame due right away
+    pub fn toggle_pause(&mut self) {
+        self.next_frame = match self.next_frame {
+            Some(_) => None,
+            None => Some(Instant::now()),
+        };
+    }
+
+    /// Index of the frame after index, scheduling the one after it. None once playback stopped at
+    /// the out point
+    pub fn advance(&mut self, index: usize, now: Instant) -> Option<usize> {
+        let (next, forward) = self.step(index, self.forward)?;
+        self.forward = forward;
+        if let Some(due) = self.next_frame {
+            // Frames that could not keep up are shown late rather than skipped
+            let next_due = due + self.frame_time();
+            self.next_frame = Some(if next_due < now { now } else { next_due });
+        }
+        Some(next)
+    }
+
+    /// Indices of the frames played after index over the time frames are decoded ahead for
+    pub fn upcoming(&self, index: usize) -> Vec<usize> {
+        let count = (BUFFER_AHEAD.as_secs_f64() * self.fps as f64).ceil() as usize;
+        let range = self.last - self.first + 1;
+        let mut frames = Vec::new();
+        let (mut index, mut forward) = (index, self.forward);
+        for _ in 0..count {
+            match self.step(index, forward) {
+                Some((next, next_forward)) => {
+                    // Frames of a short loop come around again and are only decoded once
+                    if !frames.contains(&next) {
+                        frames.push(next);
+                    }
+                    index = next;
+                    forward = next_forward;
+                }
+                None => break,
+            }
+            if frames.len() == range {
+                break;
+            }
+        }
+        frames
+    }
+
+    /// Frame after index moving forward or backward, along with the direction after it
+    fn step(&self, index: usize, forward: bool) -> Option<(usize, bool)> {
+        // Playback jumps into the range from a frame outside of it
+        if index < self.first || index > self.last {
+            return Some((self.first, true));
+        }
+        match (forward, self.repeat) {
+            (true, _) if index < self.last => Some((index + 1, true)),
+            (false, _) if index > self.first => Some((index - 1, false)),
+            (true, Repeat::Once) => None,
+            (_, Repeat::Loop) => Some((self.first, true)),
+            (true, Repeat::PingPong) => Some((index.saturating_sub(1).max(self.first), false)),
+            (false, _) => Some(((index + 1).min(self.last), true)),
+        }
+    }
+
+    /// Looks for gaps in the numbering of the frames played out of images
+    pub fn find_missing(&mut self, images: &[PathBuf]) {
+        self.missing = missing(&images[self.first..=self.last]);
+    }
+
+    /// Records that the frame of path at index failed to load
+    pub fn failed(&mut self, path: &Path, index: usize) {
+        self.failed
+            .insert(frame_number(path).map_or(index as u64 + 1, |(_, n)| n));
+    }
+
+    /// Number of frames missing from the numbering and frames that failed to load
+    pub fn problems(&self) -> (usize, usize) {
+        (self.missing.len(), self.failed.len())
+    }
+
+    /// Lists the missing frames and the frames that failed to load, None when there are none
+    pub fn report(&self) -> Option<String> {
+        let failed: Vec<u64> = self.failed.iter().copied().collect();
+        match (self.missing.is_empty(), failed.is_empty()) {
+            (true, true) => None,
+            (false, true) => Some(format!("missing frames {}", ranges(&self.missing))),
+            (true, false) => Some(format!("frames {} failed to load", ranges(&failed))),
+            (false, false) => Some(format!(
+                "missing frames {}, frames {} failed to load",
+                ranges(&self.missing),
+                ranges(&failed)
+            )),
+        }
+    }
+}
+
+/// Parses a range of image numbers such as "10-200" into the indices of its first and last image,
+/// either side may be left out to start at the first or end at the last of len images
+fn parse_range(range: &str, len: usize) -> Result<(usize, usize), String> {
+    let error = || format!("\"{}\" is not a range of images from 1 to {}", range, len);
+    let (first, last) = range.split_once('-').ok_or_else(error)?;
+    let number = |text: &str, default: usize| match text {
+        "" => Ok(default),
+        _ => match text.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= len => Ok(n),
+            _ => Err(error()),
+        },
+    };
+    let (first, last) = (number(first, 1)?, number(last, len)?);
+    if first > last {
+        return Err(error());
+    }
+    Ok((first - 1, last - 1))
+}
+
+/// Splits the file name of path into the text before its trailing number and the number, such as
+/// ("frame_", 12) for frame_0012.png
+fn frame_number(path: &Path) -> Option<(String, u64)> {
+    let stem = path.file_stem()?.to_str()?;
+    let prefix = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+    let number = stem[prefix.len()..].parse().ok()?;
+    let folder = path
+        .parent()
+        .map(|p| p.to_string_lossy())
+        .unwrap_or_default();
+    Some((format!("{}/{}", folder, prefix), number))
+}
+
+/// Frame numbers skipped between neighbouring frames of the same sequence
+fn missing(frames: &[PathBuf]) -> Vec<u64> {
+    let numbers: Vec<Option<(String, u64)>> = frames.iter().map(|f| frame_number(f)).collect();
+    let mut missing = Vec::new();
+    for pair in numbers.windows(2) {
+        if let [Some((prefix_a, a)), Some((prefix_b, b))] = pair {
+            if prefix_a == prefix_b && b > a {
+                missing.extend(a + 1..*b);
+            }
+        }
+    }
+    missing
+}
+
+/// Formats sorted numbers with runs collapsed, such as "5, 17-19"
+fn ranges(numbers: &[u64]) -> String {
+    let mut runs: Vec<(u64, u64)> = Vec::new();
+    for &n in numbers {
+        match runs.last_mut() {
+            Some((_, end)) if *end + 1 == n => *end = n,
+            _ => runs.push((n, n)),
+        }
+    }
+    runs.iter()
+        .map(|&(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{missing, ranges, Flipbook, Repeat};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_parse_flipbook_arguments() {
+        let flipbook = Flipbook::parse("", 10).unwrap();
+        assert_eq!((flipbook.fps, flipbook.repeat), (24, Repeat::Loop));
+        assert_eq!((flipbook.first, flipbook.last), (0, 9));
+        let flipbook = Flipbook::parse("pingpong 3-5 60", 10).unwrap();
+        assert_eq!((flipbook.fps, flipbook.repeat), (60, Repeat::PingPong));
+        assert_eq!((flipbook.first, flipbook.last), (2, 4));
+        let flipbook = Flipbook::parse("8-", 10).unwrap();
+        assert_eq!((flipbook.first, flipbook.last), (7, 9));
+        assert!(Flipbook::parse("25", 10).is_err());
+        assert!(Flipbook::parse("5-3", 10).is_err());
+        assert!(Flipbook::parse("1-11", 10).is_err());
+        assert!(Flipbook::parse("", 0).is_err());
+    }
+
+    #[test]
+    fn test_playback_modes_at_the_out_point() {
+        let now = Instant::now();
+        let mut flipbook = Flipbook::parse("once 2-4", 10).unwrap();
+        assert_eq!(flipbook.advance(0, now), Some(1));
+        assert_eq!(flipbook.advance(1, now), Some(2));
+        assert_eq!(flipbook.advance(3, now), None);
+        flipbook.repeat = Repeat::Loop;
+        assert_eq!(flipbook.advance(3, now), Some(1));
+        flipbook.repeat = Repeat::PingPong;
+        assert_eq!(flipbook.advance(3, now), Some(2));
+        assert_eq!(flipbook.advance(2, now), Some(1));
+        assert_eq!(flipbook.advance(1, now), Some(2));
+    }
+
+    #[test]
+    fn test_upcoming_follows_playback() {
+        let flipbook = Flipbook::parse("pingpong 24 1-5", 10).unwrap();
+        assert_eq!(flipbook.upcoming(2), vec![3, 4, 2, 1, 0]);
+        let flipbook = Flipbook::parse("once 24", 10).unwrap();
+        assert_eq!(flipbook.upcoming(7), vec![8, 9]);
+        assert_eq!(Flipbook::parse("60", 100).unwrap().upcoming(0).len(), 30);
+    }
+
+    #[test]
+    fn test_late_frames_are_not_skipped() {
+        let start = Instant::now();
+        let mut flipbook = Flipbook::parse("24", 10).unwrap();
+        let late = start + Duration::from_secs(1);
+        assert!(flipbook.is_due(late));
+        assert_eq!(flipbook.advance(0, late), Some(1));
+        assert_eq!(flipbook.until_due(late), Some(Duration::from_secs(0)));
+        flipbook.toggle_pause();
+        assert!(flipbook.is_paused() && !flipbook.is_due(late));
+    }
+
+    #[test]
+    fn test_missing_and_failed_frames_are_reported() {
+        let frames: Vec<PathBuf> = ["f_0001.png", "f_0002.png", "f_0005.png", "f_0007.png"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(missing(&frames), vec![3, 4, 6]);
+        assert_eq!(ranges(&[3, 4, 6]), "3-4, 6");
+        let mut flipbook = Flipbook::parse("", frames.len()).unwrap();
+        flipbook.find_missing(&frames);
+        assert_eq!(flipbook.problems(), (3, 0));
+        flipbook.failed(Path::new("f_0002.png"), 1);
+        flipbook.failed(Path::new("broken.png"), 2);
+        assert_eq!(
+            flipbook.report().unwrap(),
+            "missing frames 3-4, 6, frames 2-3 failed to load"
+        );
+    }
+}
@@ -0,0 +1,383 @@
+//! # Formats
+//!
+//! Decides which paths are images riv can display. A path counts as an image when its extension
+//! is a known image extension, or otherwise when the first bytes of the file match the signature of
+//! a supported format. FormatFilter restricts which formats are accepted and Skipped counts every
+//! path that was left out and why.
+
+use crate::archive::{self, is_archive};
+use crate::decode::{is_icon, is_raw, is_svg};
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Number of bytes read from files without a known extension to detect their format
+const SNIFF_LEN: u64 = 4096;
+
+/// Format is a family of image files riv can display
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// JPEG images
+    Jpeg,
+    /// PNG and APNG images
+    Png,
+    /// Windows bitmaps
+    Bmp,
+    /// WebP images, still and animated
+    Webp,
+    /// GIF images, still and animated
+    Gif,
+    /// TIFF images, single and multi-page
+    Tiff,
+    /// SVG vector images, plain and gzip compressed
+    Svg,
+    /// Camera RAW files shown through their embedded JPEG preview
+    Raw,
+    /// OpenEXR high dynamic range images
+    Exr,
+    /// Radiance high dynamic range images
+    Hdr,
+    /// Truevision TGA images
+    Tga,
+    /// Netpbm images, PPM, PGM and PBM
+    Pnm,
+    /// Windows icons and cursors, every size is a page
+    Ico,
+    /// Quite OK Image format images
+    Qoi,
+}
+
+impl Format {
+    /// Every supported format
+    pub const ALL: [Format; 14] = [
+        Format::Jpeg,
+        Format::Png,
+        Format::Bmp,
+        Format::Webp,
+        Format::Gif,
+        Format::Tiff,
+        Format::Svg,
+        Format::Raw,
+        Format::Exr,
+        Format::Hdr,
+        Format::Tga,
+        Format::Pnm,
+        Format::Ico,
+        Format::Qoi,
+    ];
+
+    /// Format of files with the given extension, case insensitive
+    pub fn from_extension(extension: &str) -> Option<Format> {
+        match extension.to_lowercase().as_str() {
+            "jpg" | "jpeg" => Some(Format::Jpeg),
+            "png" => Some(Format::Png),
+            "bmp" => Some(Format::Bmp),
+            "webp" => Some(Format::Webp),
+            "gif" => Some(Format::Gif),
+            "tif" | "tiff" => Some(Format::Tiff),
+            "svg" | "svgz" => Some(Format::Svg),
+            "cr2" | "nef" | "arw" | "dng" => Some(Format::Raw),
+            "exr" => Some(Format::Exr),
+            "hdr" => Some(Format::Hdr),
+            "tga" => Some(Format::Tga),
+            "pnm" | "ppm" | "pgm" | "pbm" => Some(Format::Pnm),
+            "ico" | "cur" => Some(Format::Ico),
+            "qoi" => Some(Format::Qoi),
+            _ => None,
+        }
+    }
+
+    /// Format of a file from its first bytes. TGA has no signature and is only found by extension
+    pub fn sniff(head: &[u8]) -> Option<Format> {
+        if head.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Format::Jpeg)
+        } else if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Format::Png)
+        } else if head.starts_with(b"BM") {
+            Some(Format::Bmp)
+        } else if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WEBP") {
+            Some(Format::Webp)
+        } else if head.starts_with(b"GIF87a") || head.starts_with(b"GIF89a") {
+            Some(Format::Gif)
+        } else if head.starts_with(b"qoif") {
+            Some(Format::Qoi)
+        } else if is_pnm(head) {
+            Some(Format::Pnm)
+        } else if is_icon(head) {
+            Some(Format::Ico)
+        } else if head.starts_with(&[0x76, 0x2F, 0x31, 0x01]) {
+            Some(Format::Exr)
+        } else if head.starts_with(b"#?RADIANCE") || head.starts_with(b"#?RGBE") {
+            Some(Format::Hdr)
+        } else if head.starts_with(b"II\x2A\x00") || head.starts_with(b"MM\x00\x2A") {
+            if is_raw(head) {
+                Some(Format::Raw)
+            } else {
+                Some(Format::Tiff)
+            }
+        } else if !head.starts_with(&[0x1F, 0x8B]) && is_svg(head) {
+            // Any gzip file would pass as compressed SVG, only trust the svgz extension for those
+            Some(Format::Svg)
+        } else {
+            None
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Format::Jpeg => "jpeg",
+            Format::Png => "png",
+            Format::Bmp => "bmp",
+            Format::Webp => "webp",
+            Format::Gif => "gif",
+            Format::Tiff => "tiff",
+            Format::Svg => "svg",
+            Format::Raw => "raw",
+            Format::Exr => "exr",
+            Format::Hdr => "hdr",
+            Format::Tga => "tga",
+            Format::Pnm => "pnm",
+            Format::Ico => "ico",
+            Format::Qoi => "qoi",
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        let s = s.to_lowercase();
+        Format::ALL
+            .iter()
+            .copied()
+            .find(|format| format.name() == s)
+            .or_else(|| Format::from_extension(&s))
+            .ok_or_else(|| {
+                let names: Vec<&str> = Format::ALL.iter().map(|f| f.name()).collect();
+                format!(
+                    "Unknown format \"{}\", expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Skipped counts the paths that were not added as images, by reason
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Skipped {
+    /// files that are not in any supported format
+    pub not_image: usize,
+    /// images in a format excluded by the FormatFilter
+    pub excluded: usize,
+    /// files that could not be read to detect their format
+    pub unreadable: usize,
+}
+
+impl Skipped {
+    /// Total number of skipped files
+    pub fn total(&self) -> usize {
+        self.not_image + self.excluded + self.unreadable
+    }
+
+    /// Describes how many files were skipped and why, None when nothing was skipped
+    pub fn report(&self) -> Option<String> {
+        if self.total() == 0 {
+            return None;
+        }
+        let reasons: Vec<String> = [
+            (self.not_image, "not images"),
+            (self.excluded, "excluded by --formats"),
+            (self.unreadable, "unreadable"),
+        ]
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, reason)| format!("{} {}", count, reason))
+        .collect();
+        Some(format!(
+            "skipped {} files: {}",
+            self.total(),
+            reasons.join(", ")
+        ))
+    }
+}
+
+/// FormatFilter holds which formats are accepted as images
+#[derive(Clone, Debug, PartialEq)]
+pub struct FormatFilter {
+    accepted: Vec<Format>,
+}
+
+impl Default for FormatFilter {
+    /// Accepts every supported format
+    fn default() -> Self {
+        FormatFilter {
+            accepted: Format::ALL.to_vec(),
+        }
+    }
+}
+
+impl FromStr for FormatFilter {
+    type Err = String;
+
+    /// Parses a comma separated list of formats. Listed formats are the only ones accepted, formats
+    /// prefixed with "-" are excluded, a list of only exclusions accepts every other format
+    fn from_str(s: &str) -> Result<FormatFilter, String> {
+        let mut allowed = Vec::new();
+        let mut denied = Vec::new();
+        for item in s.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            if let Some(item) = item.strip_prefix('-') {
+                denied.push(Format::from_str(item)?);
+            } else {
+                allowed.push(Format::from_str(item)?);
+            }
+        }
+        if allowed.is_empty() && denied.is_empty() {
+            return Err("No formats given".to_string());
+        }
+        if allowed.is_empty() {
+            allowed = Format::ALL.to_vec();
+        }
+        allowed.retain(|format| !denied.contains(format));
+        Ok(FormatFilter { accepted: allowed })
+    }
+}
+
+impl FormatFilter {
+    /// Whether images of the format are accepted
+    pub fn accepts(&self, format: Format) -> bool {
+        self.accepted.contains(&format)
+    }
+
+    /// Pushes the path to images if it is an image in an accepted format, otherwise counts it in
+    /// skipped. Archives push every image inside them instead. Directories are ignored without
+    /// being counted
+    pub fn push(&self, images: &mut Vec<PathBuf>, skipped: &mut Skipped, path: PathBuf) {
+        if is_archive(&path) {
+            match archive::entries(&path) {
+                Ok(entries) => {
+                    for (entry, format) in entries {
+                        self.push_format(images, skipped, entry, format);
+                    }
+                }
+                Err(_) => skipped.unreadable += 1,
+            }
+            return;
+        }
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Format::from_extension);
+        let format = match extension {
+            Some(format) => Some(format),
+            None if path.is_dir() => return,
+            None => match sniff_file(&path) {
+                Ok(format) => format,
+                Err(_) => {
+                    skipped.unreadable += 1;
+                    return;
+                }
+            },
+        };
+        self.push_format(images, skipped, path, format);
+    }
+
+    /// Pushes the path to images if its format is accepted, otherwise counts it in skipped
+    fn push_format(
+        &self,
+        images: &mut Vec<PathBuf>,
+        skipped: &mut Skipped,
+        path: PathBuf,
+        format: Option<Format>,
+    ) {
+        match format {
+            Some(format) if self.accepts(format) => images.push(path),
+            Some(_) => skipped.excluded += 1,
+            None => skipped.not_image += 1,
+        }
+    }
+}
+
+/// Whether the bytes start with the magic number of a binary or plain PPM, PGM or PBM image
+fn is_pnm(head: &[u8]) -> bool {
+    match head {
+        [b'P', b'1'..=b'6', space, ..] => space.is_ascii_whitespace(),
+        _ => false,
+    }
+}
+
+/// Detects the format of the file at path from its first bytes
+fn sniff_file(path: &Path) -> std::io::Result<Option<Format>> {
+    let mut head = Vec::new();
+    File::open(path)?.take(SNIFF_LEN).read_to_end(&mut head)?;
+    Ok(Format::sniff(&head))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Format, FormatFilter, Skipped};
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_sniff_detects_signatures() {
+        assert_eq!(Format::sniff(b"\xFF\xD8\xFF\xE0"), Some(Format::Jpeg));
+        assert_eq!(Format::sniff(b"RIFF\0\0\0\0WEBPVP8 "), Some(Format::Webp));
+        assert_eq!(
+            Format::sniff(b"II\x2A\x00\x08\x00\x00\x00"),
+            Some(Format::Tiff)
+        );
+        assert_eq!(Format::sniff(b"<?xml?><svg/>"), Some(Format::Svg));
+        assert_eq!(Format::sniff(b"\x76\x2F\x31\x01"), Some(Format::Exr));
+        assert_eq!(Format::sniff(b"#?RADIANCE\n"), Some(Format::Hdr));
+        assert_eq!(Format::sniff(b"P6\n2 2\n255\n"), Some(Format::Pnm));
+        assert_eq!(Format::sniff(b"P6x"), None);
+        assert_eq!(Format::sniff(b"\0\0\x02\0\x01\0"), Some(Format::Ico));
+        assert_eq!(Format::sniff(b"qoif"), Some(Format::Qoi));
+        assert_eq!(Format::sniff(b"\x1F\x8B\x08"), None);
+        assert_eq!(Format::sniff(b"hello"), None);
+    }
+
+    #[test]
+    fn test_format_filter_allow_list() {
+        let filter = FormatFilter::from_str("jpg, PNG").unwrap();
+        assert!(filter.accepts(Format::Jpeg));
+        assert!(filter.accepts(Format::Png));
+        assert!(!filter.accepts(Format::Raw));
+    }
+
+    #[test]
+    fn test_format_filter_deny_list() {
+        let filter = FormatFilter::from_str("-raw,-svg").unwrap();
+        assert!(filter.accepts(Format::Jpeg));
+        assert!(!filter.accepts(Format::Raw));
+        assert!(!filter.accepts(Format::Svg));
+        assert!(FormatFilter::from_str("jpeg,heic").is_err());
+        assert!(FormatFilter::from_str(",").is_err());
+    }
+
+    #[test]
+    fn test_push_counts_skipped_paths() {
+        let filter = FormatFilter::from_str("-gif").unwrap();
+        let mut images = Vec::new();
+        let mut skipped = Skipped::default();
+        filter.push(&mut images, &mut skipped, PathBuf::from("a.JPG"));
+        filter.push(&mut images, &mut skipped, PathBuf::from("b.gif"));
+        filter.push(&mut images, &mut skipped, PathBuf::from("missing_file"));
+        filter.push(&mut images, &mut skipped, PathBuf::from("src"));
+        assert_eq!(images, vec![PathBuf::from("a.JPG")]);
+        assert_eq!(
+            skipped.report(),
+            Some("skipped 2 files: 1 excluded by --formats, 1 unreadable".to_string())
+        );
+    }
+}
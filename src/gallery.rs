@@ -0,0 +1,139 @@
+//! # Gallery
+//!
+//! Lays out the images as a grid of thumbnails sized to the window and moves the selection
+//! through it. The grid scrolls by whole rows, only as far as needed to keep the selected image
+//! in view.
+
+use crate::ui::SelectAction;
+use sdl2::rect::Rect;
+use std::ops::Range;
+
+/// Largest side of a thumbnail in pixels
+pub const THUMBNAIL_SIZE: u32 = 192;
+/// Space between the thumbnails and around the grid in pixels
+const GAP: u32 = 16;
+
+/// Grid is the layout of the thumbnails that fit in the window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Grid {
+    /// number of thumbnails in a row
+    pub columns: usize,
+    /// number of rows that fit in the window
+    pub rows: usize,
+    /// left edge of the first column, which centers the grid in the window
+    left: i32,
+}
+
+impl Grid {
+    /// Grid of the thumbnails that fit in width by height pixels, at least one
+    pub fn new(width: u32, height: u32) -> Self {
+        let pitch = THUMBNAIL_SIZE + GAP;
+        let columns = (width.saturating_sub(GAP) / pitch).max(1);
+        let rows = (height.saturating_sub(GAP) / pitch).max(1);
+        let left = (width as i32 - (columns * pitch - GAP) as i32) / 2;
+        Grid {
+            columns: columns as usize,
+            rows: rows as usize,
+            left,
+        }
+    }
+
+    /// First row to show so the selected image is in view, scrolling as little as possible away
+    /// from top_row
+    pub fn scroll(&self, top_row: usize, selected: usize) -> usize {
+        let row = selected / self.columns;
+        if row < top_row {
+            row
+        } else if row >= top_row + self.rows {
+            row + 1 - self.rows
+        } else {
+            top_row
+        }
+    }
+
+    /// Indices of the images shown when top_row is the first row, out of len images
+    pub fn visible(&self, top_row: usize, len: usize) -> Range<usize> {
+        let start = top_row.saturating_mul(self.columns).min(len);
+        let end = (top_row + self.rows).saturating_mul(self.columns).min(len);
+        start..end
+    }
+
+    /// Cell the thumbnail of the image at index is drawn in when top_row is the first row
+    pub fn cell(&self, top_row: usize, index: usize) -> Rect {
+        let row = (index / self.columns).saturating_sub(top_row) as i32;
+        let column = (index % self.columns) as i32;
+        let pitch = (THUMBNAIL_SIZE + GAP) as i32;
+        Rect::new(
+            self.left + column * pitch,
+            GAP as i32 + row * pitch,
+            THUMBNAIL_SIZE,
+            THUMBNAIL_SIZE,
+        )
+    }
+
+    /// Index reached moving `times` steps from index in direction, stopping at the first and last
+    /// of len images
+    pub fn moved(&self, index: usize, len: usize, direction: &SelectAction, times: usize) -> usize {
+        let last = len.saturating_sub(1);
+        let page = self.columns * self.rows;
+        let (step, forward) = match direction {
+            SelectAction::Left => (1, false),
+            SelectAction::Right => (1, true),
+            SelectAction::Up => (self.columns, false),
+            SelectAction::Down => (self.columns, true),
+            SelectAction::PageUp => (page, false),
+            SelectAction::PageDown => (page, true),
+            // A count selects the image with that number
+            SelectAction::Number => return times.saturating_sub(1).min(last),
+        };
+        let distance = step.saturating_mul(times);
+        if forward {
+            index.saturating_add(distance).min(last)
+        } else {
+            index.saturating_sub(distance)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Grid, GAP, THUMBNAIL_SIZE};
+    use crate::ui::SelectAction;
+
+    fn grid(columns: u32, rows: u32) -> Grid {
+        let pitch = THUMBNAIL_SIZE + GAP;
+        Grid::new(columns * pitch + GAP, rows * pitch + GAP)
+    }
+
+    #[test]
+    fn test_grid_fits_window() {
+        let grid = grid(4, 3);
+        assert_eq!((grid.columns, grid.rows), (4, 3));
+        assert_eq!(grid.cell(0, 0).x(), GAP as i32);
+        assert_eq!(grid.cell(0, 5).y(), grid.cell(0, 1).bottom() + GAP as i32);
+        let tiny = Grid::new(10, 10);
+        assert_eq!((tiny.columns, tiny.rows), (1, 1));
+    }
+
+    #[test]
+    fn test_scroll_keeps_selection_in_view() {
+        let grid = grid(4, 3);
+        assert_eq!(grid.scroll(0, 11), 0);
+        assert_eq!(grid.scroll(0, 12), 1);
+        assert_eq!(grid.scroll(5, 12), 3);
+        assert_eq!(grid.visible(1, 14), 4..14);
+        assert_eq!(grid.visible(4, 14), 14..14);
+    }
+
+    #[test]
+    fn test_moved_stops_at_ends() {
+        let grid = grid(4, 3);
+        assert_eq!(grid.moved(5, 30, &SelectAction::Down, 2), 13);
+        assert_eq!(grid.moved(5, 30, &SelectAction::Up, 2), 0);
+        assert_eq!(grid.moved(5, 30, &SelectAction::Left, 1), 4);
+        assert_eq!(grid.moved(5, 30, &SelectAction::Right, 100), 29);
+        assert_eq!(grid.moved(5, 30, &SelectAction::PageDown, 1), 17);
+        assert_eq!(grid.moved(5, 30, &SelectAction::Number, 12), 11);
+        assert_eq!(grid.moved(5, 30, &SelectAction::Number, 99), 29);
+    }
+}
@@ -2,8 +2,9 @@
 //!
 //! Module InfoBar provides structures and functions for building and rendering an infobar
 
+use crate::flipbook::{Flipbook, Repeat};
 use crate::paths::Paths;
-use crate::ui::{Mode, State};
+use crate::ui::{ColorProfile, Mode, Playback, State};
 
 /// Text contains the strings required to print the infobar.
 pub struct Text {
@@ -17,8 +18,12 @@ pub struct Text {
 
 impl Text {
     /// Updates the infobar based on the current mode of the application
-    /// Normal Mode:
-    ///     mode = index of current image
+    /// Normal and Gallery Mode:
+    ///     mode = index of current image, followed by the page for multi-page images, the
+    ///            frame for animated images, whether the image has a color profile, how many
+    ///            images were found while the glob is still running, the frame rate of the
+    ///            flipbook, the seconds left on the current image of a slideshow and how many
+    ///            images are marked. A count typed in the gallery replaces it
     ///     information = path to current image
     /// Command Mode:
     ///     mode = "Command"
@@ -29,7 +34,7 @@ impl Text {
     pub fn update(current_mode: &Mode, paths: &Paths, state: &State) -> Self {
         let (mode, information) = match current_mode {
             Mode::Command(msg) => ("Command".to_string(), format!(":{}", msg)),
-            Mode::Normal => {
+            Mode::Normal | Mode::Gallery => {
                 let information = match paths.current_image_path() {
                     Some(path) => match path.to_str() {
                         Some(name) => name.to_string(),
@@ -38,10 +43,64 @@ impl Text {
                     None => "No file selected".to_string(),
                 };
 
-                let mode = match paths.current_image() {
-                    Some(current) => format!("{} of {}", current, paths.max_viewable().unwrap()),
-                    None => "No files in path".to_string(),
+                let mut mode = match (paths.current_image(), state.scanning) {
+                    (Some(current), None) => {
+                        format!("{} of {}", current, paths.max_viewable().unwrap())
+                    }
+                    (Some(current), Some(found)) => format!(
+                        "{} of {} (scanning… {} found)",
+                        current,
+                        paths.max_viewable().unwrap(),
+                        found
+                    ),
+                    (None, Some(found)) => format!("scanning… {} found", found),
+                    (None, None) => "No files in path".to_string(),
                 };
+                if paths.page_count() > 1 {
+                    mode.push_str(&format!(
+                        " (page {}/{})",
+                        paths.page() + 1,
+                        paths.page_count()
+                    ));
+                }
+                if state.playback.is_animated() {
+                    mode.push_str(&playback_text(&state.playback));
+                }
+                match state.color_profile {
+                    ColorProfile::Untagged => (),
+                    ColorProfile::Tagged => mode.push_str(" (ICC)"),
+                    ColorProfile::Converted => mode.push_str(" (ICC, converted)"),
+                }
+                if state.tone.hdr {
+                    mode.push_str(&format!(
+                        " (exposure {:+.1}, gamma {:.1})",
+                        state.tone.exposure, state.tone.gamma
+                    ));
+                }
+                if let Some(flipbook) = &state.flipbook {
+                    mode.push_str(&flipbook_text(flipbook));
+                }
+                if let Some(slideshow) = &state.slideshow {
+                    if slideshow.is_paused() {
+                        mode.push_str(" (slideshow paused)");
+                    } else {
+                        mode.push_str(&format!(" (slideshow {}s)", slideshow.countdown()));
+                    }
+                }
+                let marked = paths.marked_count();
+                if marked > 0 {
+                    let current = paths.index().is_some_and(|i| paths.is_marked(i));
+                    if current {
+                        mode.push_str(&format!(" (marked, {} marked)", marked));
+                    } else {
+                        mode.push_str(&format!(" ({} marked)", marked));
+                    }
+                }
+
+                // A count typed in the gallery is shown until the action it repeats
+                if *current_mode == Mode::Gallery && state.gallery.count > 0 {
+                    mode = state.gallery.count.to_string();
+                }
 
                 (mode, information)
             }
@@ -56,3 +115,43 @@ impl Text {
         Text { information, mode }
     }
 }
+
+/// Describes flipbook playback, for example " (flipbook 24 fps, loop 1-240, 2 missing)"
+fn flipbook_text(flipbook: &Flipbook) -> String {
+    let repeat = match flipbook.repeat {
+        Repeat::Once => "once",
+        Repeat::Loop => "loop",
+        Repeat::PingPong => "ping-pong",
+    };
+    let mut text = format!(
+        " (flipbook {} fps, {} {}-{}",
+        flipbook.fps,
+        repeat,
+        flipbook.first + 1,
+        flipbook.last + 1
+    );
+    if flipbook.is_paused() {
+        text.push_str(", paused");
+    }
+    let (missing, failed) = flipbook.problems();
+    if missing > 0 {
+        text.push_str(&format!(", {} missing", missing));
+    }
+    if failed > 0 {
+        text.push_str(&format!(", {} failed", failed));
+    }
+    text.push(')');
+    text
+}
+
+/// Describes the frame being played, for example " (frame 2/12, paused)"
+fn playback_text(playback: &Playback) -> String {
+    let mut text = format!(" (frame {}/{}", playback.frame + 1, playback.frame_count);
+    if playback.paused {
+        text.push_str(", paused");
+    } else if (playback.speed - 1.0).abs() > f32::EPSILON {
+        text.push_str(&format!(", {}x", playback.speed));
+    }
+    text.push(')');
+    text
+}
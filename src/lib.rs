@@ -17,12 +17,25 @@ extern crate regex;
 #[macro_use]
 extern crate lazy_static;
 
+pub mod archive;
+pub mod cache;
 pub mod cli;
+pub mod decode;
+pub mod filmstrip;
+pub mod flipbook;
+pub mod formats;
+pub mod gallery;
 pub mod infobar;
 pub mod paths;
+pub mod prefetch;
 pub mod program;
+pub mod scan;
 pub mod screen;
+pub mod slideshow;
 pub mod sort;
+pub mod stdin;
+pub mod thumbnails;
+pub mod tiles;
 pub mod ui;
 
 use regex::Regex;
@@ -1,6 +1,7 @@
 //! Paths contains the Paths struct which contains all path related information required for the
 //! running of the program.
 
+use std::collections::{BTreeMap, HashSet};
 use std::ops::RangeInclusive;
 use std::path::PathBuf;
 use std::slice::SliceIndex;
@@ -67,6 +68,10 @@ impl PathsBuilder {
             index: self.index,
             art_len: self.art_len,
             art_len_orig: self.art_len_orig,
+            page: 0,
+            page_count: 1,
+            marked: HashSet::new(),
+            bookmarks: BTreeMap::new(),
         }
     }
 }
@@ -86,6 +91,16 @@ pub struct Paths {
     art_len: usize,
     /// Original Artificial length of the vector
     art_len_orig: Option<usize>,
+    /// page is the index of the page displayed within the current image
+    page: usize,
+    /// page_count is the number of pages in the current image, known once it is decoded
+    page_count: usize,
+    /// marked is the paths of the images marked for file operations, they stay marked however
+    /// the images are reordered
+    marked: HashSet<PathBuf>,
+    /// bookmarks is the path of the image stored under each bookmark letter, a bookmark stays on
+    /// its image however the images are reordered
+    bookmarks: BTreeMap<char, PathBuf>,
 }
 
 impl Paths {
@@ -124,6 +139,9 @@ impl Paths {
                 self.max_viewable_index().unwrap()
             );
         }
+        if self.index != Some(i) {
+            self.reset_page();
+        }
         self.index = Some(i);
     }
 
@@ -167,6 +185,11 @@ impl Paths {
     pub fn reload_images(&mut self, new_images: Vec<PathBuf>) {
         // Replace the image collection with new ones
         self.images = new_images;
+        self.reset_page();
+        // Images that are still there stay marked and bookmarked
+        let images: HashSet<&PathBuf> = self.images.iter().collect();
+        self.marked.retain(|path| images.contains(path));
+        self.bookmarks.retain(|_, path| images.contains(path));
 
         // Make sure to reset the index
         match self.images.len() {
@@ -184,8 +207,23 @@ impl Paths {
         }
     }
 
+    /// Adds images found after the program started to the end of the images, the first added
+    /// image becomes the current image when there was none
+    pub fn append_images(&mut self, new_images: Vec<PathBuf>) {
+        self.images.extend(new_images);
+        self.art_len = match self.art_len_orig {
+            Some(orig_art_len) => std::cmp::min(orig_art_len, self.images.len()),
+            None => self.images.len(),
+        };
+        if self.index.is_none() && !self.images.is_empty() {
+            self.index = Some(0);
+        }
+    }
+
     /// Reverses images and updates index to keep current image as index
     pub fn reverse(&mut self) {
+        let current_path = self.current_image_path().cloned();
+        let (page, page_count) = (self.page, self.page_count);
         self.images.reverse();
         let max_viewable_index = match self.max_viewable_index() {
             Some(i) => i,
@@ -195,6 +233,11 @@ impl Paths {
         // Safe to unwrap as there is at least one image
         let reversed_index = max_viewable_index - self.index.unwrap();
         self.set_index(reversed_index);
+        // Stay on the same page if the current image did not change
+        if self.current_image_path() == current_path.as_ref() {
+            self.page = page;
+            self.page_count = page_count;
+        }
     }
 
     /// Returns current index and image path
@@ -248,7 +291,12 @@ impl Paths {
 
         assert!(index < len);
         // Remove image
-        self.images.remove(index);
+        let path = self.images.remove(index);
+        self.marked.remove(&path);
+        self.bookmarks.retain(|_, bookmarked| *bookmarked != path);
+        if self.index == Some(index) {
+            self.reset_page();
+        }
         // Decrease artificial length
         self.art_len = self.art_len.saturating_sub(1);
 
@@ -258,11 +306,12 @@ impl Paths {
             self.index = None;
             return;
         }
-        // Adjust index if past bounds
-        if index >= new_len {
-            // Set current image index to None if no images left
-            // Otherwise decrement the index normally
-            self.index = self.index.unwrap().checked_sub(1);
+        // Keep the current image when an image before it is removed, and the index in bounds
+        // when the last image is removed
+        if let Some(current) = self.index {
+            if index < current || current >= new_len {
+                self.index = current.checked_sub(1);
+            }
         }
     }
 
@@ -274,12 +323,90 @@ impl Paths {
         }
     }
 
+    /// Marks the image at index or unmarks it when it is marked
+    pub fn toggle_mark(&mut self, index: usize) {
+        if let Some(path) = self.images.get(index) {
+            if !self.marked.remove(path) {
+                self.marked.insert(path.clone());
+            }
+        }
+    }
+
+    /// Whether the image at index is marked
+    pub fn is_marked(&self, index: usize) -> bool {
+        self.images
+            .get(index)
+            .is_some_and(|path| self.marked.contains(path))
+    }
+
+    /// Marks every viewable image
+    pub fn mark_all(&mut self) {
+        let len = self.max_viewable().unwrap_or(0);
+        self.marked.extend(self.images[..len].iter().cloned());
+    }
+
+    /// Marks the viewable images that are not marked and unmarks the ones that are
+    pub fn invert_marks(&mut self) {
+        for index in 0..self.max_viewable().unwrap_or(0) {
+            self.toggle_mark(index);
+        }
+    }
+
+    /// Unmarks every image
+    pub fn clear_marks(&mut self) {
+        self.marked.clear();
+    }
+
+    /// Number of marked images
+    pub fn marked_count(&self) -> usize {
+        self.marked.len()
+    }
+
+    /// Indices of the marked viewable images in order
+    pub fn marked_indices(&self) -> Vec<usize> {
+        let len = self.max_viewable().unwrap_or(0);
+        (0..len).filter(|&index| self.is_marked(index)).collect()
+    }
+
+    /// Stores the current image under letter, replacing the image stored there before. Returns
+    /// false when there is no current image
+    pub fn set_bookmark(&mut self, letter: char) -> bool {
+        match self.current_image_path().cloned() {
+            Some(path) => {
+                self.bookmarks.insert(letter, path);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Index of the image stored under letter, None when nothing is stored there or the image is
+    /// past the maximum number of images viewable
+    pub fn bookmark(&self, letter: char) -> Option<usize> {
+        let path = self.bookmarks.get(&letter)?;
+        let len = self.max_viewable()?;
+        self.images[..len].iter().position(|image| image == path)
+    }
+
+    /// Letters of the bookmarks in order along with the index of the image stored under each,
+    /// leaving out the images past the maximum number of images viewable
+    pub fn bookmarks(&self) -> Vec<(char, usize)> {
+        self.bookmarks
+            .keys()
+            .filter_map(|&letter| Some((letter, self.bookmark(letter)?)))
+            .collect()
+    }
+
     /// Decrement which image is currently tracked
     /// Floors at the first image (0 index)
     /// Does nothing if no images are present
     pub fn decrement(&mut self, step: usize) {
         if let Some(index) = self.index() {
-            self.index = Some(index.saturating_sub(step));
+            let new_index = index.saturating_sub(step);
+            if new_index != index {
+                self.reset_page();
+            }
+            self.index = Some(new_index);
         }
     }
 
@@ -294,11 +421,11 @@ impl Paths {
         let max_viewable_index = self.max_viewable_index().unwrap();
 
         // Cap new index at artificial length
-        if new_index >= max_viewable_index {
-            self.index = Some(max_viewable_index);
-        } else {
-            self.index = Some(new_index);
+        let new_index = std::cmp::min(new_index, max_viewable_index);
+        if self.index != Some(new_index) {
+            self.reset_page();
         }
+        self.index = Some(new_index);
     }
 
     /// Update at runtime the maximum images to display at once
@@ -315,13 +442,49 @@ impl Paths {
         // Cap index if new max is smaller
         if self.index > self.max_viewable_index() {
             self.index = self.max_viewable_index();
+            self.reset_page();
         }
     }
+
+    /// Index of the page displayed within the current image
+    pub fn page(&self) -> usize {
+        self.page
+    }
+
+    /// Number of pages in the current image
+    pub fn page_count(&self) -> usize {
+        self.page_count
+    }
+
+    /// Updates the number of pages once the current image is decoded
+    /// Caps the page at the last page
+    pub fn set_page_count(&mut self, page_count: usize) {
+        self.page_count = std::cmp::max(page_count, 1);
+        self.page = std::cmp::min(self.page, self.page_count - 1);
+    }
+
+    /// Advance which page of the current image is displayed
+    /// Caps at the last page
+    pub fn next_page(&mut self, step: usize) {
+        self.page = std::cmp::min(self.page.saturating_add(step), self.page_count - 1);
+    }
+
+    /// Decrement which page of the current image is displayed
+    /// Floors at the first page
+    pub fn prev_page(&mut self, step: usize) {
+        self.page = self.page.saturating_sub(step);
+    }
+
+    /// Moves back to the first page, the page count is unknown until the new image is decoded
+    fn reset_page(&mut self) {
+        self.page = 0;
+        self.page_count = 1;
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::paths::PathsBuilder;
+    use crate::paths::{Paths, PathsBuilder};
     use std::iter::repeat;
     use std::path::PathBuf;
 
@@ -330,6 +493,82 @@ mod tests {
         PathsBuilder::new(images, "./keep".into(), ".".into())
     }
 
+    fn numbered_paths(n: usize) -> Paths {
+        let images = (0..n)
+            .map(|i| PathBuf::from(format!("{}.png", i)))
+            .collect();
+        PathsBuilder::new(images, "./keep".into(), ".".into()).build()
+    }
+
+    #[test]
+    fn test_marks_follow_paths_when_reordered() {
+        let mut images = numbered_paths(4);
+        images.toggle_mark(1);
+        images.toggle_mark(3);
+        assert_eq!(images.marked_indices(), vec![1, 3]);
+        images.reverse();
+        assert_eq!(images.marked_indices(), vec![0, 2]);
+        images.images_as_mut_slice().sort();
+        assert_eq!(images.marked_indices(), vec![1, 3]);
+        images.invert_marks();
+        assert_eq!(images.marked_indices(), vec![0, 2]);
+        images.mark_all();
+        assert_eq!(images.marked_count(), 4);
+        images.clear_marks();
+        assert!(!images.is_marked(0));
+    }
+
+    #[test]
+    fn test_bookmarks_follow_paths() {
+        let mut images = numbered_paths(5);
+        images.set_index(3);
+        assert!(images.set_bookmark('a'));
+        images.set_index(1);
+        assert!(images.set_bookmark('b'));
+        images.reverse();
+        assert_eq!(images.bookmark('a'), Some(1));
+        assert_eq!(images.bookmarks(), vec![('a', 1), ('b', 3)]);
+        assert_eq!(images.bookmark('c'), None);
+        // Bookmarks of images that are gone are dropped
+        images.remove_image(1);
+        assert_eq!(images.bookmark('a'), None);
+        let reloaded = vec![PathBuf::from("1.png"), PathBuf::from("9.png")];
+        images.reload_images(reloaded);
+        assert_eq!(images.bookmarks(), vec![('b', 0)]);
+    }
+
+    #[test]
+    fn test_bookmarks_past_maximum_viewable_are_left_out() {
+        let images = (0..5)
+            .map(|i| PathBuf::from(format!("{}.png", i)))
+            .collect();
+        let mut images = PathsBuilder::new(images, "./keep".into(), ".".into())
+            .with_maximum_viewable(2)
+            .build();
+        assert!(images.set_bookmark('a'));
+        let reordered = (0..5)
+            .rev()
+            .map(|i| PathBuf::from(format!("{}.png", i)))
+            .collect();
+        images.reload_images(reordered);
+        assert_eq!(images.bookmark('a'), None);
+        assert!(images.bookmarks().is_empty());
+    }
+
+    #[test]
+    fn test_removing_marked_image_before_current_keeps_current() {
+        let mut images = numbered_paths(4);
+        images.set_index(2);
+        images.toggle_mark(0);
+        images.remove_image(0);
+        assert_eq!(images.current_image_path(), Some(&PathBuf::from("2.png")));
+        assert_eq!(images.marked_count(), 0);
+        images.remove_image(2);
+        assert_eq!(images.current_image_path(), Some(&PathBuf::from("2.png")));
+        images.remove_image(1);
+        assert_eq!(images.current_image_path(), Some(&PathBuf::from("1.png")));
+    }
+
     #[test]
     fn test_setting_index_with_safe_caps_index_at_end_or_artificial_len() {
         let mut images = dummy_paths_builder(50).with_maximum_viewable(10).build();
@@ -499,6 +738,19 @@ mod tests {
         assert_eq!(images.art_len_orig, None);
     }
 
+    #[test]
+    fn test_appending_images_keeps_index_and_artificial_len() {
+        let mut paths = dummy_paths_builder(0).with_maximum_viewable(3).build();
+        paths.append_images(vec![PathBuf::from("a.png"), PathBuf::from("b.png")]);
+        assert_eq!(paths.index(), Some(0));
+        assert_eq!(paths.max_viewable(), Some(2));
+        paths.increment(1);
+        paths.append_images(vec![PathBuf::from("c.png"), PathBuf::from("d.png")]);
+        assert_eq!(paths.index(), Some(1));
+        assert_eq!(paths.max_viewable(), Some(3));
+        assert_eq!(paths.images().len(), 4);
+    }
+
     #[test]
     fn test_replacing_images_resets_index() {
         let mut images = dummy_paths_builder(50).build();
@@ -519,4 +771,57 @@ mod tests {
         images.reload_images(less_images.images);
         assert_eq!(images.max_viewable(), Some(10));
     }
+
+    #[test]
+    fn test_next_page_caps_at_last_page() {
+        let mut images = dummy_paths_builder(10).build();
+        images.set_page_count(5);
+        images.next_page(3);
+        assert_eq!(images.page(), 3);
+        images.next_page(3);
+        assert_eq!(images.page(), 4);
+        images.prev_page(9);
+        assert_eq!(images.page(), 0);
+    }
+
+    #[test]
+    fn test_changing_image_resets_page() {
+        let mut images = dummy_paths_builder(10).build();
+        images.set_page_count(5);
+        images.next_page(2);
+        images.increment(1);
+        assert_eq!(images.page(), 0);
+        assert_eq!(images.page_count(), 1);
+    }
+
+    #[test]
+    fn test_moving_within_bounds_keeps_page() {
+        let mut images = dummy_paths_builder(10).build();
+        images.set_page_count(5);
+        images.next_page(2);
+        // Already at the first image
+        images.decrement(1);
+        images.set_index(0);
+        images.reverse();
+        images.reverse();
+        assert_eq!(images.page(), 2);
+    }
+
+    #[test]
+    fn test_removing_current_image_resets_page() {
+        let mut images = dummy_paths_builder(10).build();
+        images.set_page_count(5);
+        images.next_page(2);
+        images.remove_current_image();
+        assert_eq!(images.page(), 0);
+    }
+
+    #[test]
+    fn test_fewer_pages_caps_page() {
+        let mut images = dummy_paths_builder(1).build();
+        images.set_page_count(5);
+        images.next_page(4);
+        images.set_page_count(2);
+        assert_eq!(images.page(), 1);
+    }
 }
@@ -0,0 +1,279 @@
+//! # Prefetch
+//!
+//! Decodes the images around the current image on background threads, so moving to one of them
+//! only needs a texture upload. Images ahead in the direction of travel are decoded before the
+//! ones behind. Only the first page of an image is prefetched.
+
+use crate::decode::{self, Image};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+/// Most threads decoding images in the background
+const MAX_WORKERS: usize = 4;
+
+/// Direction the user last moved through the images in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    /// Towards the last image
+    Forward,
+    /// Towards the first image
+    Backward,
+}
+
+/// Queue is the work shared between the prefetcher and its workers
+#[derive(Default)]
+struct Queue {
+    /// paths around the current image, the only ones worth keeping decoded
+    wanted: Vec<PathBuf>,
+    /// paths waiting for a worker, the most wanted first
+    pending: VecDeque<PathBuf>,
+    /// paths a worker is decoding
+    decoding: Vec<PathBuf>,
+    /// decoded images waiting to be displayed
+    decoded: HashMap<PathBuf, Result<Image, String>>,
+    /// bumped on every cancel, images decoded for an older generation are dropped
+    generation: u64,
+    /// workers exit once the prefetcher is dropped
+    stopped: bool,
+}
+
+/// Shared holds the queue along with the condition workers and take wait on
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn wait<'a>(&self, queue: MutexGuard<'a, Queue>) -> MutexGuard<'a, Queue> {
+        self.changed
+            .wait(queue)
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Prefetcher decodes the images around the current image with a pool of worker threads
+pub struct Prefetcher {
+    shared: Arc<Shared>,
+    /// number of images before and after the current image to decode, 0 turns prefetching off
+    window: usize,
+    /// number of worker threads started
+    workers: usize,
+    /// index of the image the last window was around
+    last_index: Option<usize>,
+    /// direction of travel the last window followed
+    direction: Direction,
+}
+
+impl Prefetcher {
+    /// Starts the workers decoding `window` images before and after the current image
+    pub fn new(window: usize) -> Self {
+        let mut prefetcher = Prefetcher {
+            shared: Arc::new(Shared::default()),
+            window,
+            workers: 0,
+            last_index: None,
+            direction: Direction::Forward,
+        };
+        prefetcher.start_workers(window * 2);
+        prefetcher
+    }
+
+    /// Starts workers until there are count of them, at most one per core up to MAX_WORKERS
+    fn start_workers(&mut self, count: usize) {
+        let parallelism = thread::available_parallelism().map_or(1, |n| n.get());
+        let count = count.min(parallelism).min(MAX_WORKERS);
+        while self.workers < count {
+            let shared = Arc::clone(&self.shared);
+            thread::spawn(move || work(&shared));
+            self.workers += 1;
+        }
+    }
+
+    /// Queues the images around index for decoding, following the direction the index moved in
+    /// since the last call. Decoded images outside of the new window are dropped
+    pub fn prefetch(&mut self, images: &[PathBuf], index: usize) {
+        if self.window == 0 {
+            return;
+        }
+        match self.last_index {
+            Some(last) if index > last => self.direction = Direction::Forward,
+            Some(last) if index < last => self.direction = Direction::Backward,
+            _ => (),
+        }
+        self.last_index = Some(index);
+        let wanted = window(index, images.len(), self.window, self.direction)
+            .into_iter()
+            .map(|i| images[i].clone())
+            .collect();
+        self.queue(wanted);
+    }
+
+    /// Queues the images at indices for decoding in that order, for playback that knows which
+    /// images it shows next. Decoded images not among them are dropped. Workers are started even
+    /// when prefetching is off
+    pub fn buffer(&mut self, images: &[PathBuf], indices: &[usize]) {
+        self.start_workers(MAX_WORKERS);
+        let wanted = indices.iter().map(|&i| images[i].clone()).collect();
+        self.queue(wanted);
+    }
+
+    /// Makes wanted the images to keep decoded, queueing the ones not decoded yet in order
+    fn queue(&self, wanted: Vec<PathBuf>) {
+        let mut queue = self.shared.lock();
+        queue.decoded.retain(|path, _| wanted.contains(path));
+        let pending: VecDeque<PathBuf> = wanted
+            .iter()
+            .filter(|path| !queue.decoded.contains_key(*path) && !queue.decoding.contains(path))
+            .cloned()
+            .collect();
+        queue.pending = pending;
+        queue.wanted = wanted;
+        self.shared.changed.notify_all();
+    }
+
+    /// Takes the decoded first page of the image at path, waiting for it when a worker is still
+    /// decoding it. None when it was not prefetched
+    pub fn take(&self, path: &PathBuf) -> Option<Result<Image, String>> {
+        let mut queue = self.shared.lock();
+        while queue.decoding.contains(path) {
+            queue = self.shared.wait(queue);
+        }
+        queue.pending.retain(|pending| pending != path);
+        queue.wanted.retain(|wanted| wanted != path);
+        queue.decoded.remove(path)
+    }
+
+    /// Drops every queued and decoded image, images being decoded are dropped once done. The next
+    /// prefetch starts without a direction of travel
+    pub fn cancel(&mut self) {
+        let mut queue = self.shared.lock();
+        queue.wanted.clear();
+        queue.pending.clear();
+        queue.decoded.clear();
+        queue.generation = queue.generation.wrapping_add(1);
+        self.last_index = None;
+        self.direction = Direction::Forward;
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        self.shared.lock().stopped = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+/// Decodes queued images until the prefetcher is dropped
+fn work(shared: &Shared) {
+    let mut queue = shared.lock();
+    loop {
+        if queue.stopped {
+            return;
+        }
+        let path = match queue.pending.pop_front() {
+            Some(path) => path,
+            None => {
+                queue = shared.wait(queue);
+                continue;
+            }
+        };
+        queue.decoding.push(path.clone());
+        let generation = queue.generation;
+        drop(queue);
+
+        let image = decode::open(&path, 0);
+
+        queue = shared.lock();
+        queue.decoding.retain(|decoding| decoding != &path);
+        if queue.generation == generation && queue.wanted.contains(&path) {
+            queue.decoded.insert(path, image);
+        }
+        shared.changed.notify_all();
+    }
+}
+
+/// Indices of the `size` images before and after index among len images, nearest first, with the
+/// images ahead in the direction of travel before the ones behind
+fn window(index: usize, len: usize, size: usize, direction: Direction) -> Vec<usize> {
+    let after = (1..=size).filter_map(|step| index.checked_add(step).filter(|&i| i < len));
+    let before = (1..=size).filter_map(|step| index.checked_sub(step));
+    match direction {
+        Direction::Forward => after.chain(before).collect(),
+        Direction::Backward => before.chain(after).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{window, Direction, Prefetcher};
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_window_follows_direction_of_travel() {
+        assert_eq!(window(5, 10, 2, Direction::Forward), vec![6, 7, 4, 3]);
+        assert_eq!(window(5, 10, 2, Direction::Backward), vec![4, 3, 6, 7]);
+        assert_eq!(window(0, 3, 2, Direction::Forward), vec![1, 2]);
+        assert_eq!(window(2, 3, 2, Direction::Backward), vec![1, 0]);
+    }
+
+    #[test]
+    fn test_prefetch_decodes_neighbours() {
+        let images = vec![
+            PathBuf::from("tests/fixtures/rgb.ppm"),
+            PathBuf::from("tests/fixtures/gray.pgm"),
+            PathBuf::from("tests/fixtures/missing.png"),
+        ];
+        let mut prefetcher = Prefetcher::new(1);
+        prefetcher.prefetch(&images, 0);
+        for _ in 0..1000 {
+            if prefetcher.shared.lock().decoded.contains_key(&images[1]) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        let image = prefetcher.take(&images[1]).unwrap().unwrap();
+        assert_eq!(image.frames[0].width, 2);
+        assert!(prefetcher.take(&images[1]).is_none());
+        assert!(prefetcher.take(&images[0]).is_none());
+    }
+
+    #[test]
+    fn test_buffer_keeps_playback_order() {
+        let images = vec![
+            PathBuf::from("a.png"),
+            PathBuf::from("b.png"),
+            PathBuf::from("c.png"),
+        ];
+        let mut prefetcher = Prefetcher::new(0);
+        prefetcher.buffer(&images, &[2, 0]);
+        assert!(prefetcher.workers > 0);
+        assert_eq!(
+            prefetcher.shared.lock().wanted,
+            vec![images[2].clone(), images[0].clone()]
+        );
+    }
+
+    #[test]
+    fn test_cancel_drops_queued_images() {
+        let images = vec![PathBuf::from("a.png"), PathBuf::from("b.png")];
+        let mut prefetcher = Prefetcher::new(0);
+        prefetcher.window = 1;
+        prefetcher.prefetch(&images, 1);
+        assert_eq!(prefetcher.direction, Direction::Forward);
+        prefetcher.prefetch(&images, 0);
+        assert_eq!(prefetcher.direction, Direction::Backward);
+        assert_eq!(prefetcher.shared.lock().pending.len(), 1);
+        prefetcher.cancel();
+        assert!(prefetcher.shared.lock().pending.is_empty());
+        assert_eq!(prefetcher.last_index, None);
+    }
+}
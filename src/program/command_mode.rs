@@ -1,10 +1,16 @@
 //! File that contains Command mode functionality, command mode is a mode that allows verbose input
 //! from the user to perform tasks or edit stored data in the application during runtime
 use super::Program;
+use crate::decode;
+use crate::flipbook::Flipbook;
+use crate::formats::{FormatFilter, Skipped};
+use crate::slideshow::Slideshow;
 use crate::sort::SortOrder;
+use crate::stdin::{self, is_stdin};
 use crate::ui::{process_command_mode, Action, HelpRender, Mode};
 use regex::Regex;
 use shellexpand::full;
+use std::fs;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::time::{Duration, Instant};
@@ -48,6 +54,37 @@ enum Commands {
     ///
     /// Sets the maximum number of images to display at any given time
     MaximumImages,
+    /// `:ar` or `:autorotate`
+    ///
+    /// No argument: toggles rotating images upright from their EXIF orientation.
+    /// One argument: "on" or "off" turns it on or off
+    AutoRotate,
+    /// `:ex` or `:export`
+    ///
+    /// No argument: saves the current image to the destination folder.
+    /// One argument: the file or folder to save the current image to.
+    /// Images read from standard input or inside archives are saved as files this way
+    Export,
+    /// `:ss` or `:slideshow`
+    ///
+    /// No argument: starts a slideshow of 5 seconds per image, or stops the running one.
+    /// Arguments: the seconds to show each image for, "loop" to start over after the last image
+    /// and "shuffle" to show the images in a random order
+    Slideshow,
+    /// `:fb` or `:flipbook`
+    ///
+    /// No argument: plays the images in a loop at 24 frames per second, or stops playing them.
+    /// Arguments: the frame rate of 24, 30 or 60, "once", "loop" or "pingpong" for what happens
+    /// after the last frame and a range of image numbers to play such as 10-200
+    Flipbook,
+    /// `:bm` or `:bookmarks`
+    ///
+    /// Toggles the list of bookmarks with the image stored under each letter
+    Bookmarks,
+    /// `:dbg` or `:debug`
+    ///
+    /// Toggles the hit and miss counts and memory use of the texture caches
+    Debug,
 }
 
 impl FromStr for Commands {
@@ -63,6 +100,12 @@ impl FromStr for Commands {
             "r" | "reverse" => Ok(Commands::Reverse),
             "df" | "destfolder" => Ok(Commands::DestFolder),
             "m" | "max" => Ok(Commands::MaximumImages),
+            "ar" | "autorotate" => Ok(Commands::AutoRotate),
+            "ex" | "export" => Ok(Commands::Export),
+            "ss" | "slideshow" => Ok(Commands::Slideshow),
+            "fb" | "flipbook" => Ok(Commands::Flipbook),
+            "bm" | "bookmarks" => Ok(Commands::Bookmarks),
+            "dbg" | "debug" => Ok(Commands::Debug),
             _ => Err(format!(
                 "No such command \"{}\", type :? for command help",
                 s
@@ -71,17 +114,16 @@ impl FromStr for Commands {
     }
 }
 
-/// Globs the passed path, returning an error if no images are in that path, glob::glob fails, or
-/// path is unexpected
-fn glob_path(path: &PathBuf) -> Result<Vec<PathBuf>, String> {
-    use crate::cli::push_image_path;
-
+/// Globs the passed path keeping the images in an accepted format, returning an error if no
+/// images are in that path, glob::glob fails, or path is unexpected
+fn glob_path(path: &PathBuf, formats: &FormatFilter) -> Result<(Vec<PathBuf>, Skipped), String> {
     let mut new_images: Vec<PathBuf> = Vec::new();
+    let mut skipped = Skipped::default();
     let path_matches = glob::glob(&path.to_string_lossy()).map_err(|e| e.to_string())?;
     for path in path_matches {
         match path {
             Ok(p) => {
-                push_image_path(&mut new_images, p);
+                formats.push(&mut new_images, &mut skipped, p);
             }
             Err(e) => {
                 let err_msg = format!("Unexpected path {}", e);
@@ -90,10 +132,33 @@ fn glob_path(path: &PathBuf) -> Result<Vec<PathBuf>, String> {
         }
     }
     if new_images.is_empty() {
-        let err_msg = format!("Path \"{}\" had no images", path.display());
+        let mut err_msg = format!("Path \"{}\" had no images", path.display());
+        if let Some(report) = skipped.report() {
+            err_msg.push_str(&format!(", {}", report));
+        }
         return Err(err_msg);
     }
-    Ok(new_images)
+    Ok((new_images, skipped))
+}
+
+/// Expands environment variables and ~ in a path typed by the user
+/// On Unix escaped characters are unescaped for example: folder\ path -> folder path
+fn expand_path(input: &str) -> Result<PathBuf, String> {
+    let mut path = full(input)
+        .map_err(|e| format!("\"{}\": {}", e.var_name, e.cause))?
+        .to_string();
+    if cfg!(unix) {
+        lazy_static! {
+            static ref REGEX_REMOVE_ESCAPED_CHARS: Regex = match Regex::new(r"\\(.)") {
+                Ok(regex) => regex,
+                Err(e) => panic!("Logic Error: {}", e),
+            };
+        }
+        path = REGEX_REMOVE_ESCAPED_CHARS
+            .replace_all(&path, "$1")
+            .to_string();
+    }
+    Ok(PathBuf::from(path))
 }
 
 /// Separate user input into the main command and its respected arguments
@@ -164,8 +229,8 @@ impl<'a> Program<'a> {
             }
         };
         let msg = path_to_newglob.to_owned();
-        let new_images = match glob_path(&path) {
-            Ok(new_images) => new_images,
+        let (new_images, skipped) = match glob_path(&path, &self.formats) {
+            Ok(found) => found,
             Err(e) => {
                 self.ui_state.mode = Mode::Error(e.to_string());
                 return;
@@ -180,7 +245,14 @@ impl<'a> Program<'a> {
             None => None,
         };
 
+        // The range of a flipbook is numbered by the images that are replaced
+        self.stop_flipbook();
+        // Images the glob given on the command line is still finding are no longer wanted
+        self.scanner = None;
+        self.ui_state.scanning = None;
         self.paths.reload_images(new_images);
+        self.prefetcher.cancel();
+        self.screen.cache.clear();
 
         // Set current directory to new one
         let new_base_dir = crate::new_base_dir(&path);
@@ -205,11 +277,13 @@ impl<'a> Program<'a> {
             }
         }
 
-        self.ui_state.mode = Mode::Success(format!(
-            "found {} images in {}",
-            self.paths.images().len(),
-            msg
-        ));
+        self.prefetch();
+
+        let mut success_msg = format!("found {} images in {}", self.paths.images().len(), msg);
+        if let Some(report) = skipped.report() {
+            success_msg.push_str(&format!(", {}", report));
+        }
+        self.ui_state.mode = Mode::Success(success_msg);
         self.ui_state.rerender_time = Some(Instant::now());
     }
 
@@ -219,7 +293,9 @@ impl<'a> Program<'a> {
     /// Additional argument changes the sorting method and sorts the images
     fn sort(&mut self, arguments: String) {
         if arguments.is_empty() {
+            self.prefetcher.cancel();
             self.sorter.sort(self.paths.images_as_mut_slice());
+            self.prefetch();
             return;
         }
         // get a SortOrder from the provided argument
@@ -233,6 +309,7 @@ impl<'a> Program<'a> {
         };
         self.sorter.set_order(new_sort_order);
 
+        self.prefetcher.cancel();
         self.sorter.sort(self.paths.images_as_mut_slice());
 
         // the path to find in order to maintain that it is the current image
@@ -259,6 +336,7 @@ impl<'a> Program<'a> {
         } else {
             self.paths.set_index(0);
         }
+        self.prefetch();
     }
 
     /// sets the new maximum_viewable images
@@ -273,6 +351,120 @@ impl<'a> Program<'a> {
         self.paths.set_actual_maximum(new_actual_max);
     }
 
+    /// Starts a slideshow with the interval and options in arguments, stopping the running one
+    /// without arguments
+    fn slideshow(&mut self, arguments: &str) {
+        if arguments.is_empty() && self.ui_state.slideshow.take().is_some() {
+            self.ui_state.mode = Mode::Success("slideshow stopped".to_string());
+            self.ui_state.rerender_time = Some(Instant::now());
+            return;
+        }
+        match Slideshow::parse(arguments) {
+            Ok(slideshow) => {
+                let mut msg = format!(
+                    "slideshow started, {}s per image",
+                    slideshow.interval.as_secs_f64()
+                );
+                if slideshow.looping {
+                    msg.push_str(", looping");
+                }
+                if slideshow.shuffle {
+                    msg.push_str(", shuffled");
+                }
+                self.stop_flipbook();
+                self.ui_state.slideshow = Some(slideshow);
+                self.ui_state.mode = Mode::Success(msg);
+                self.ui_state.rerender_time = Some(Instant::now());
+            }
+            Err(e) => self.ui_state.mode = Mode::Error(e),
+        }
+    }
+
+    /// Plays the images as a flipbook with the frame rate, repeat and range in arguments,
+    /// stopping the flipbook playing without arguments
+    fn flipbook(&mut self, arguments: &str) {
+        if arguments.is_empty() && self.ui_state.flipbook.is_some() {
+            self.stop_flipbook();
+            return;
+        }
+        let mut flipbook = match Flipbook::parse(arguments, self.paths.max_viewable().unwrap_or(0))
+        {
+            Ok(flipbook) => flipbook,
+            Err(e) => {
+                self.ui_state.mode = Mode::Error(e);
+                return;
+            }
+        };
+        flipbook.find_missing(self.paths.images());
+        let mut msg = format!(
+            "flipbook playing images {}-{} at {} fps",
+            flipbook.first + 1,
+            flipbook.last + 1,
+            flipbook.fps
+        );
+        if let Some(report) = flipbook.report() {
+            msg.push_str(&format!(", {}", report));
+        }
+        self.ui_state.slideshow = None;
+        self.ui_state.flipbook = Some(flipbook);
+        self.prefetch();
+        self.ui_state.mode = Mode::Success(msg);
+        self.ui_state.rerender_time = Some(Instant::now());
+    }
+
+    /// Turns rotating images upright from their EXIF orientation on or off, toggling it without
+    /// an argument. The current image is loaded again to apply it
+    fn auto_rotate(&mut self, arguments: &str) {
+        let auto_rotate = match arguments {
+            "" => !self.ui_state.auto_rotate,
+            "on" => true,
+            "off" => false,
+            _ => {
+                self.ui_state.mode =
+                    Mode::Error(format!("\"{}\" is not \"on\" or \"off\"", arguments));
+                return;
+            }
+        };
+        self.ui_state.auto_rotate = auto_rotate;
+        self.screen.dirty = true;
+        let state = if auto_rotate { "on" } else { "off" };
+        self.ui_state.mode = Mode::Success(format!("auto rotation turned {}", state));
+        self.ui_state.rerender_time = Some(Instant::now());
+    }
+
+    /// Saves the bytes of the current image to a file, existing files are not overwritten
+    fn export(&mut self, arguments: &str) -> Result<String, String> {
+        let current = match self.paths.current_image_path() {
+            Some(path) => path,
+            None => return Err("no image to export".to_string()),
+        };
+        let file_name = if is_stdin(current) {
+            stdin::file_name()
+        } else {
+            match current.file_name() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => return Err("failed to read filename for current image".to_string()),
+            }
+        };
+        let mut target = if arguments.is_empty() {
+            self.paths.dest_folder.clone()
+        } else {
+            self.paths.base_dir.join(expand_path(arguments)?)
+        };
+        if arguments.is_empty() || target.is_dir() {
+            target.push(file_name);
+        }
+        if target.exists() {
+            return Err(format!("{} already exists", target.display()));
+        }
+        let bytes = decode::read(current)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        fs::write(&target, bytes).map_err(|e| e.to_string())?;
+        Ok(format!("exported image to {}", target.display()))
+    }
+
     /// Enters command mode that gets user input and runs a set of possible commands based on user input.
     /// After every command the user is set either into normal mode or the app terminates.
     ///
@@ -309,6 +501,14 @@ impl<'a> Program<'a> {
                 HelpRender::Command => self.ui_state.render_help = HelpRender::None,
                 _ => self.ui_state.render_help = HelpRender::Command,
             },
+            Commands::Bookmarks => match self.ui_state.render_help {
+                HelpRender::Bookmarks => self.ui_state.render_help = HelpRender::None,
+                _ => self.ui_state.render_help = HelpRender::Bookmarks,
+            },
+            Commands::Debug => match self.ui_state.render_help {
+                HelpRender::Debug => self.ui_state.render_help = HelpRender::None,
+                _ => self.ui_state.render_help = HelpRender::Debug,
+            },
             Commands::Quit => {
                 self.ui_state.mode = Mode::Exit;
             }
@@ -322,30 +522,16 @@ impl<'a> Program<'a> {
                     );
                     return Ok(());
                 }
-                match full(&arguments) {
+                match expand_path(&arguments) {
                     Ok(path) => {
-                        let mut path = path.to_string();
-                        if cfg!(unix) {
-                            lazy_static! {
-                                static ref REGEX_REMOVE_ESCAPED_CHARS: Regex =
-                                    match Regex::new(r"\\(.)") {
-                                        Ok(regex) => regex,
-                                        Err(e) => panic!("Logic Error: {}", e),
-                                    };
-                            }
-                            path = REGEX_REMOVE_ESCAPED_CHARS
-                                .replace_all(&path, "$1")
-                                .to_string();
-                        }
                         let success_msg =
-                            format!("destination folder successfully set to {}", path);
-                        self.paths.dest_folder = PathBuf::from(path);
+                            format!("destination folder successfully set to {}", path.display());
+                        self.paths.dest_folder = path;
                         self.ui_state.mode = Mode::Success(success_msg);
                         self.ui_state.rerender_time = Some(Instant::now());
                     }
                     Err(e) => {
-                        self.ui_state.mode =
-                            Mode::Error(format!("\"{}\": {}", e.var_name, e.cause));
+                        self.ui_state.mode = Mode::Error(e);
                         return Ok(());
                     }
                 }
@@ -362,6 +548,24 @@ impl<'a> Program<'a> {
             Commands::Sort => {
                 self.sort(arguments);
             }
+            Commands::AutoRotate => {
+                self.auto_rotate(&arguments);
+            }
+            Commands::Slideshow => {
+                self.slideshow(&arguments);
+            }
+            Commands::Flipbook => {
+                self.flipbook(&arguments);
+            }
+            Commands::Export => match self.export(&arguments) {
+                Ok(msg) => {
+                    self.ui_state.mode = Mode::Success(msg);
+                    self.ui_state.rerender_time = Some(Instant::now());
+                }
+                Err(e) => {
+                    self.ui_state.mode = Mode::Error(format!("Failed to export image: {}", e));
+                }
+            },
         }
         Ok(())
     }
@@ -6,11 +6,24 @@
 mod command_mode;
 mod render;
 pub use self::render::*;
+use crate::archive;
+use crate::cache::TextureCache;
 use crate::cli;
+use crate::decode::ColorManager;
+use crate::filmstrip::Filmstrip;
+use crate::formats::FormatFilter;
+use crate::gallery::{Grid, THUMBNAIL_SIZE};
 use crate::paths::{Paths, PathsBuilder};
+use crate::prefetch::Prefetcher;
+use crate::scan::Scanner;
 use crate::screen::Screen;
 use crate::sort::Sorter;
-use crate::ui::{self, Action, Mode, PanAction, ProcessAction, RotationDirection, ZoomAction};
+use crate::stdin::is_stdin;
+use crate::thumbnails::Thumbnailer;
+use crate::ui::{
+    self, Action, BookmarkAction, FrameAction, MarkAction, Mode, PageAction, PanAction,
+    ProcessAction, RotAngle, RotationDirection, SelectAction, SpeedAction, ToneAction, ZoomAction,
+};
 use core::cmp;
 use fs_extra::file::copy;
 use fs_extra::file::move_file;
@@ -30,6 +43,8 @@ use std::time::{Duration, Instant};
 
 const FONT_SIZE: u16 = 18;
 const PAN_PIXELS: f32 = 50.0;
+/// Bytes of memory the thumbnails of the gallery may take up
+const THUMBNAIL_CACHE_SIZE: usize = 128 * 1024 * 1024;
 
 /// Program contains all information needed to run the event loop and render the images to screen
 pub struct Program<'a> {
@@ -37,6 +52,11 @@ pub struct Program<'a> {
     paths: Paths,
     ui_state: ui::State<'a>,
     sorter: Sorter,
+    formats: FormatFilter,
+    color_manager: Option<ColorManager>,
+    prefetcher: Prefetcher,
+    scanner: Option<Scanner>,
+    thumbnailer: Thumbnailer,
 }
 
 impl<'a> Program<'a> {
@@ -57,6 +77,10 @@ impl<'a> Program<'a> {
         let sort_order = args.sort_order;
         let max_length = args.max_length;
         let base_dir = args.base_dir;
+        let formats = args.formats;
+        let color_manager = args.color_manager;
+        let prefetcher = Prefetcher::new(args.prefetch);
+        let scanner = args.scanner;
 
         let max_viewable = max_length;
 
@@ -85,6 +109,11 @@ impl<'a> Program<'a> {
         let paths = PathsBuilder::new(images, dest_folder, base_dir)
             .with_maximum_viewable(max_viewable)
             .build();
+        // Report skipped files on the infobar until the first rerender
+        let (mode, rerender_time) = match args.skipped.report() {
+            Some(report) => (Mode::Success(report), Some(Instant::now())),
+            None => (Mode::Normal, None),
+        };
         Ok(Program {
             screen: Screen {
                 sdl_context,
@@ -94,14 +123,30 @@ impl<'a> Program<'a> {
                 mono_font,
                 last_index: None,
                 last_texture: None,
+                last_image: None,
+                texture_scale: 1.0,
+                shown: None,
+                cache: TextureCache::new(args.cache_size.saturating_mul(1024 * 1024)),
+                thumbnails: TextureCache::new(THUMBNAIL_CACHE_SIZE),
                 dirty: false,
+                failed: None,
             },
             paths,
             ui_state: ui::State {
                 fullscreen: args.fullscreen,
+                auto_rotate: args.auto_rotate,
+                mode,
+                rerender_time,
+                scanning: scanner.as_ref().map(Scanner::found),
+                slideshow: args.slideshow,
                 ..Default::default()
             },
             sorter,
+            formats,
+            color_manager,
+            prefetcher,
+            scanner,
+            thumbnailer: Thumbnailer::new(THUMBNAIL_SIZE),
         })
     }
 
@@ -125,21 +170,14 @@ impl<'a> Program<'a> {
 
     // Calculates the scale required to fit large images to screen
     fn calculate_scale_for_fit(&self) -> f32 {
-        if let Some(tex) = self.screen.last_texture.as_ref() {
-            let query = tex.query();
-            let (src_x, src_y) = (query.width, query.height);
+        if let Some((src_x, src_y)) = self.natural_size() {
+            // A quarter turn swaps the width and height the image is displayed with
+            let (src_x, src_y) = match self.ui_state.rot_angle {
+                RotAngle::Left | RotAngle::Right => (src_y, src_x),
+                RotAngle::Up | RotAngle::Down => (src_x, src_y),
+            };
             let target = self.screen.canvas.viewport();
-            let (dst_x, dst_y) = (target.width(), target.height());
-            // case 1: both source dimensions smaller
-            if src_x < dst_x && src_y < dst_y {
-                return 1.0;
-            }
-            // case 2: source aspect ratio is larger
-            if src_x as f32 / src_y as f32 > dst_x as f32 / dst_y as f32 {
-                return dst_x as f32 / src_x as f32;
-            }
-            // case 3: source aspect ratio is smaller
-            dst_y as f32 / src_y as f32
+            fit_scale(src_x, src_y, target.width() as f32, target.height() as f32)
         } else {
             1.0
         }
@@ -170,6 +208,26 @@ impl<'a> Program<'a> {
         self.render_screen(false)
     }
 
+    /// Moves to a later page of the current image without changing the image
+    fn next_page(&mut self, step: usize) -> Result<(), String> {
+        let page = self.paths.page();
+        self.paths.next_page(step);
+        if page != self.paths.page() {
+            self.screen.dirty = true;
+        }
+        self.render_screen(false)
+    }
+
+    /// Moves to an earlier page of the current image without changing the image
+    fn prev_page(&mut self, step: usize) -> Result<(), String> {
+        let page = self.paths.page();
+        self.paths.prev_page(step);
+        if page != self.paths.page() {
+            self.screen.dirty = true;
+        }
+        self.render_screen(false)
+    }
+
     /// Skips forward by the default skip increment and renders the image
     pub fn skip_forward(&mut self, times: usize) -> Result<(), String> {
         let skip_size = compute_skip_size(self.paths.images());
@@ -263,11 +321,269 @@ impl<'a> Program<'a> {
         self.render_screen(false)
     }
 
+    /// Pauses or resumes playback of an animated image
+    fn toggle_playback(&mut self) -> Result<(), String> {
+        let playback = &mut self.ui_state.playback;
+        if !playback.is_animated() {
+            return Ok(());
+        }
+        playback.paused = !playback.paused;
+        // Resume from the start of the current frame
+        playback.frame_start = Instant::now();
+        self.render_screen(false)
+    }
+
+    /// Steps forward `times` frames of an animated image
+    fn next_frame(&mut self, times: usize) -> Result<(), String> {
+        if !self.ui_state.playback.is_animated() {
+            return Ok(());
+        }
+        self.ui_state.playback.next_frame(times);
+        self.update_frame_texture()?;
+        self.render_screen(false)
+    }
+
+    /// Steps back `times` frames of an animated image
+    fn prev_frame(&mut self, times: usize) -> Result<(), String> {
+        if !self.ui_state.playback.is_animated() {
+            return Ok(());
+        }
+        self.ui_state.playback.prev_frame(times);
+        self.update_frame_texture()?;
+        self.render_screen(false)
+    }
+
+    /// Advances an animated image to the frame that should be displayed now
+    /// Only renders if the displayed frame or paused state changed
+    fn animate(&mut self) -> Result<(), String> {
+        let image = match self.screen.last_image.as_ref() {
+            Some(image) => image,
+            None => return Ok(()),
+        };
+        let changed = self
+            .ui_state
+            .playback
+            .advance(Instant::now(), |frame| image.frames[frame].delay);
+        if !changed {
+            return Ok(());
+        }
+        self.update_frame_texture()?;
+        self.render_screen(false)
+    }
+
+    /// Shows the next image of the slideshow once the current one was shown for the interval,
+    /// and rerenders as the countdown on the infobar ticks down
+    fn advance_slideshow(&mut self) -> Result<(), String> {
+        let slideshow = match self.ui_state.slideshow.as_mut() {
+            Some(slideshow) => slideshow,
+            None => return Ok(()),
+        };
+        if !slideshow.is_due() {
+            // Messages stay up until they time out
+            let message = self.ui_state.rerender_time.is_some();
+            if slideshow.countdown_changed() && self.ui_state.render_infobar && !message {
+                self.render_screen(false)?;
+            }
+            return Ok(());
+        }
+        let next = match (self.paths.index(), self.paths.max_viewable()) {
+            (Some(index), Some(len)) => slideshow.next(index, len),
+            _ => None,
+        };
+        match next {
+            Some(index) => self.jump_to_image_index(index)?,
+            None => {
+                self.ui_state.slideshow = None;
+                self.ui_state.mode = Mode::Success("slideshow finished".to_string());
+                self.ui_state.rerender_time = Some(Instant::now());
+            }
+        }
+        self.render_screen(false)
+    }
+
+    /// Shows the next frame of the flipbook once it is due, recording frames that fail to load
+    fn play_flipbook(&mut self) -> Result<(), String> {
+        let now = Instant::now();
+        let next = match (self.ui_state.flipbook.as_mut(), self.paths.index()) {
+            (Some(flipbook), Some(index)) if flipbook.is_due(now) => flipbook.advance(index, now),
+            _ => return Ok(()),
+        };
+        let next = match next {
+            Some(next) => next,
+            None => {
+                self.stop_flipbook();
+                return self.render_screen(false);
+            }
+        };
+        self.jump_to_image_index(next)?;
+        self.render_screen(false)?;
+        // Frames that fail to load leave the previous frame shown
+        let shown = self.screen.shown.as_ref().map(|shown| &shown.key.path);
+        if let (Some(flipbook), Some(path)) = (
+            self.ui_state.flipbook.as_mut(),
+            self.paths.current_image_path(),
+        ) {
+            if shown != Some(path) {
+                flipbook.failed(path, next);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops the flipbook, reporting the frames that were missing or failed to load
+    fn stop_flipbook(&mut self) {
+        let flipbook = match self.ui_state.flipbook.take() {
+            Some(flipbook) => flipbook,
+            None => return,
+        };
+        self.ui_state.mode = match flipbook.report() {
+            Some(report) => Mode::Error(format!("flipbook stopped, {}", report)),
+            None => Mode::Success("flipbook stopped".to_string()),
+        };
+        self.ui_state.rerender_time = Some(Instant::now());
+        // Go back to decoding the images around the current one
+        self.prefetcher.cancel();
+        self.prefetch();
+    }
+
+    /// Adds the images the background glob found since the last call. Once the glob finishes the
+    /// images are sorted, keeping the current image displayed as it is
+    fn scan(&mut self) -> Result<(), String> {
+        let scanner = match self.scanner.as_mut() {
+            Some(scanner) => scanner,
+            None => return Ok(()),
+        };
+        let images = scanner.poll();
+        if !scanner.is_done() {
+            if images.is_empty() {
+                return Ok(());
+            }
+            self.ui_state.scanning = Some(scanner.found());
+            self.paths.append_images(images);
+            return self.render_screen(false);
+        }
+        let skipped = scanner.skipped().clone();
+        self.scanner = None;
+        self.ui_state.scanning = None;
+        self.paths.append_images(images);
+
+        let current = self.paths.current_image_path().cloned();
+        self.prefetcher.cancel();
+        self.sorter.sort(self.paths.images_as_mut_slice());
+        if let (Some(current), Some(max_index)) = (current, self.paths.max_viewable_index()) {
+            match self.paths.images().iter().position(|path| path == &current) {
+                Some(index) if index <= max_index => {
+                    self.paths.set_index(index);
+                    // Same image at a new index, so the texture and view are kept
+                    if self.screen.last_index.is_some() {
+                        self.screen.last_index = Some(index);
+                    }
+                }
+                _ => self.paths.set_index(0),
+            }
+        }
+        self.prefetch();
+
+        if let Some(report) = skipped.report() {
+            self.ui_state.mode = Mode::Success(report);
+            self.ui_state.rerender_time = Some(Instant::now());
+        }
+        self.render_screen(false)
+    }
+
+    /// Moves the selection of the gallery `times` steps in direction
+    fn select(&mut self, direction: &SelectAction, times: usize) -> Result<(), String> {
+        let (index, len) = match (self.paths.index(), self.paths.max_viewable()) {
+            (Some(index), Some(len)) => (index, len),
+            _ => return Ok(()),
+        };
+        let index = self.gallery_grid().moved(index, len, direction, times);
+        self.paths.set_index(index);
+        self.render_screen(false)
+    }
+
+    /// Grid of the thumbnails that fit in the window above the infobar
+    fn gallery_grid(&self) -> Grid {
+        let viewport = self.screen.canvas.viewport();
+        Grid::new(viewport.width(), viewport.height() - self.infobar_height())
+    }
+
+    /// Filmstrip along the bottom of the window, just above the infobar
+    fn filmstrip(&self) -> Filmstrip {
+        let viewport = self.screen.canvas.viewport();
+        Filmstrip::new(viewport.width(), viewport.height() - self.infobar_height())
+    }
+
+    /// Height the infobar takes up at the bottom of the window, 0 while it is hidden
+    fn infobar_height(&self) -> u32 {
+        if self.ui_state.render_infobar {
+            (self.screen.font.height().max(0) as u32).min(self.screen.canvas.viewport().height())
+        } else {
+            0
+        }
+    }
+
+    /// Jumps to the image clicked in the filmstrip, a click anywhere else toggles fit
+    fn click(&mut self, x: i32, y: i32) -> Result<(), String> {
+        let clicked = match (self.paths.index(), self.paths.max_viewable()) {
+            (Some(index), Some(len)) => self.filmstrip().hit(index, len, x, y),
+            _ => None,
+        };
+        match clicked {
+            Some(index) => {
+                self.jump_to_image_index(index)?;
+                self.render_screen(false)
+            }
+            None => self.toggle_fit(),
+        }
+    }
+
+    /// Mode input is taken in once a message was displayed, the gallery while it is open
+    fn input_mode(&self) -> Mode {
+        if self.ui_state.gallery.open {
+            Mode::Gallery
+        } else {
+            Mode::Normal
+        }
+    }
+
+    /// Queues the images around the current image for decoding in the background, or the frames
+    /// coming up while the flipbook plays
+    fn prefetch(&mut self) {
+        if let (Some(flipbook), Some(index), Some(max_index)) = (
+            self.ui_state.flipbook.as_ref(),
+            self.paths.index(),
+            self.paths.max_viewable_index(),
+        ) {
+            let mut upcoming = flipbook.upcoming(index);
+            upcoming.retain(|&i| i <= max_index);
+            self.prefetcher.buffer(self.paths.images(), &upcoming);
+            return;
+        }
+        if let (Some(index), Some(max_index)) =
+            (self.paths.index(), self.paths.max_viewable_index())
+        {
+            self.prefetcher
+                .prefetch(&self.paths.images()[..=max_index], index);
+        }
+    }
+
+    /// Width and height of the current image at a scale of 1.0, which is the size of its texture
+    /// unless the texture was rasterized at another scale
+    fn natural_size(&self) -> Option<(f32, f32)> {
+        self.screen.last_texture.as_ref().map(|tex| {
+            let query = tex.query();
+            (
+                query.width as f32 / self.screen.texture_scale,
+                query.height as f32 / self.screen.texture_scale,
+            )
+        })
+    }
+
     fn calc_x_step(&self) -> f32 {
-        if let Some(tex) = self.screen.last_texture.as_ref() {
-            let src_w = tex.query().width;
+        if let Some((src_w, _)) = self.natural_size() {
             let dst_w = self.screen.canvas.viewport().width();
-            let x_diff = (dst_w as f32 - (src_w as f32 * self.ui_state.scale)) / 2.0;
+            let x_diff = (dst_w as f32 - (src_w * self.ui_state.scale)) / 2.0;
             (PAN_PIXELS / x_diff).abs()
         } else {
             0.0
@@ -275,10 +591,9 @@ impl<'a> Program<'a> {
     }
 
     fn calc_y_step(&self) -> f32 {
-        if let Some(tex) = self.screen.last_texture.as_ref() {
-            let src_h = tex.query().height;
+        if let Some((_, src_h)) = self.natural_size() {
             let dst_h = self.screen.canvas.viewport().height();
-            let y_diff = (dst_h as f32 - (src_h as f32 * self.ui_state.scale)) / 2.0;
+            let y_diff = (dst_h as f32 - (src_h * self.ui_state.scale)) / 2.0;
             (PAN_PIXELS / y_diff).abs()
         } else {
             0.0
@@ -302,34 +617,61 @@ impl<'a> Program<'a> {
         Ok(newname)
     }
 
-    /// Copies the current image and (n-1) next images
+    /// Indices of the images file operations act on, the marked images when any are marked,
+    /// otherwise the current image and the (amount - 1) images after it
+    fn targets(&self, amount: usize) -> Vec<usize> {
+        let marked = self.paths.marked_indices();
+        if !marked.is_empty() {
+            return marked;
+        }
+        match (self.paths.index(), self.paths.max_viewable_index()) {
+            (Some(index), Some(max_index)) if amount > 0 => {
+                (index..=cmp::min(index.saturating_add(amount - 1), max_index)).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Errors if any of the images at indices are inside an archive or were read from standard
+    /// input, file operations only work on whole files
+    fn refuse_virtual(&self, indices: &[usize], verb: &str) -> Result<(), String> {
+        let images = self.paths.images();
+        match indices
+            .iter()
+            .map(|&index| &images[index])
+            .find(|path| archive::is_archived(path) || is_stdin(path))
+        {
+            Some(path) if is_stdin(path) => Err(format!(
+                "can't {} the image read from standard input, use :export to save it",
+                verb
+            )),
+            Some(path) => Err(format!(
+                "can't {} images inside the archive {}",
+                verb,
+                archive::real_path(path).display()
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Copies the marked images, or the current image and (n-1) next images when none are marked
     /// Does nothing if supplied 0 for an amount
     fn copy_images(&self, amount: usize) -> Result<String, String> {
         if amount == 0 {
             return Ok("0 images asked to copy".to_string());
         }
 
-        let current_index = match self.paths.index() {
-            Some(i) => i,
-            None => return Err("no images to copy".to_string()),
-        };
-
-        let copy_range = current_index..=(current_index.saturating_add(amount - 1));
-        let paths = self.paths.get_range(&copy_range);
-        let paths = match paths {
-            Some(paths) => paths,
-            None => {
-                return Err(format!(
-                    "Image range {}..={} is out of range",
-                    copy_range.start(),
-                    copy_range.end()
-                ))
-            }
-        };
+        let indices = self.targets(amount);
+        if indices.is_empty() {
+            return Err("no images to copy".to_string());
+        }
+        self.refuse_virtual(&indices, "copy")?;
+        let images = self.paths.images();
+        let paths: Vec<&PathBuf> = indices.iter().map(|&index| &images[index]).collect();
 
         // Store errors for possible future use
         let mut failures: Vec<String> = Vec::new();
-        for imagepath in paths {
+        for imagepath in &paths {
             let newname = match self.construct_dest_filepath(imagepath) {
                 Ok(path) => path,
                 Err(e) => {
@@ -361,40 +703,37 @@ impl<'a> Program<'a> {
         }
     }
 
-    /// Moves the current image and (n-1) next images
+    /// Moves the marked images, or the current image and (n-1) next images when none are marked
     /// Does nothing if supplied 0 for an amount
     fn move_images(&mut self, amount: usize) -> Result<String, String> {
         if amount == 0 {
             return Ok("0 images asked to move".to_string());
         }
 
-        let current_index = match self.paths.index() {
-            Some(i) => i,
-            None => return Err("no images to move".to_string()),
-        };
-
-        // Safe to unwrap as max_index is always present if index is present
-        let max_index = self.paths.max_viewable_index().unwrap();
+        let indices = self.targets(amount);
+        if indices.is_empty() {
+            return Err("no images to move".to_string());
+        }
+        self.refuse_virtual(&indices, "move")?;
 
-        // Compute actual number of images to remove
-        // Cap at max index. Add 1 incase max index == current_index
-        let total_removes =
-            std::cmp::min(current_index + amount - 1, max_index) - current_index + 1;
+        let total_removes = indices.len();
         // Store errors for possible future use
         let mut failures: Vec<String> = Vec::new();
-        for _ in 0..total_removes {
-            let current_path = self.paths.current_image_path().unwrap();
-            let newname = self.construct_dest_filepath(current_path)?;
+        // Removing the last image first keeps the indices of the others valid
+        for &index in indices.iter().rev() {
+            let current_path = self.paths.images()[index].clone();
+            let newname = self.construct_dest_filepath(&current_path)?;
             let opt = &fs_extra::file::CopyOptions::new();
 
             // Attempt to move as many images as possible
-            if let Err(e) = move_file(current_path, &newname, opt) {
+            if let Err(e) = move_file(&current_path, &newname, opt) {
                 eprintln!("{}", e);
                 failures.push(e.to_string());
                 continue;
             }
             // Only if successful, remove image from tracked images
-            self.paths.remove_current_image();
+            self.screen.forget(&current_path);
+            self.paths.remove_image(index);
         }
 
         // Moving the image automatically advanced to next image
@@ -417,31 +756,29 @@ impl<'a> Program<'a> {
         }
     }
 
-    /// Trashes image currently being viewed
+    /// Trashes the marked images, or the current image and (n-1) next images when none are
+    /// marked
     /// Does nothing if supplied 0 for an amount
     fn trash_images(&mut self, amount: usize) -> Result<String, String> {
         if amount == 0 {
             return Ok("0 images asked to trash".to_string());
         }
 
-        let current_index = match self.paths.index() {
-            Some(i) => i,
-            None => return Err("no images to trash".to_string()),
-        };
-
-        let max_index = self.paths.max_viewable_index().unwrap();
+        let indices = self.targets(amount);
+        if indices.is_empty() {
+            return Err("no images to trash".to_string());
+        }
+        self.refuse_virtual(&indices, "trash")?;
 
-        // Compute actual number of images to trash
-        // Cap at max index. Add 1 incase max index == current_index
-        let total_trashes =
-            std::cmp::min(current_index + amount - 1, max_index) - current_index + 1;
+        let total_trashes = indices.len();
 
         // Store errors for possible future use
 
         let mut failures: Vec<String> = Vec::new();
-        // Attempt to trash as many images as possible;
-        for _ in 0..total_trashes {
-            let current_path = self.paths.current_image_path().unwrap();
+        // Attempt to trash as many images as possible, the last first so the indices of the
+        // others stay valid
+        for &index in indices.iter().rev() {
+            let current_path = self.paths.images()[index].clone();
 
             #[cfg(target_os = "windows")]
             {
@@ -493,7 +830,8 @@ impl<'a> Program<'a> {
             return Err("Trash support for OS not supported".to_string());
 
             // Only if successful, remove image from tracked images
-            self.paths.remove_current_image();
+            self.screen.forget(&current_path);
+            self.paths.remove_image(index);
         }
 
         // Trashing the image automatically advanced to next image
@@ -512,37 +850,36 @@ impl<'a> Program<'a> {
         }
     }
 
-    /// Deletes image currently being viewed
+    /// Deletes the marked images, or the current image and (n-1) next images when none are
+    /// marked
     /// Does nothing if supplied 0 for an amount
     fn delete_images(&mut self, amount: usize) -> Result<String, String> {
         if amount == 0 {
             return Ok("0 images asked to delete".to_string());
         }
 
-        let current_index = match self.paths.index() {
-            Some(i) => i,
-            None => return Err("no images to delete".to_string()),
-        };
-
-        let max_index = self.paths.max_viewable_index().unwrap();
+        let indices = self.targets(amount);
+        if indices.is_empty() {
+            return Err("no images to delete".to_string());
+        }
+        self.refuse_virtual(&indices, "delete")?;
 
-        // Compute actual number of images to remove
-        // Cap at max index. Add 1 incase max index == current_index
-        let total_removes =
-            std::cmp::min(current_index + amount - 1, max_index) - current_index + 1;
+        let total_removes = indices.len();
 
         // Store errors for possible future use
         let mut failures: Vec<String> = Vec::new();
-        // Attempt to delete as many images as possible
-        for _ in 0..total_removes {
-            let current_path = self.paths.current_image_path().unwrap();
-            if let Err(e) = remove(current_path) {
+        // Attempt to delete as many images as possible, the last first so the indices of the
+        // others stay valid
+        for &index in indices.iter().rev() {
+            let current_path = self.paths.images()[index].clone();
+            if let Err(e) = remove(&current_path) {
                 eprintln!("{}", e);
                 failures.push(e.to_string());
                 continue;
             }
             // Only if successful, remove image from tracked images
-            self.paths.remove_current_image();
+            self.screen.forget(&current_path);
+            self.paths.remove_image(index);
         }
 
         // Deletes the image automatically advanced to next image
@@ -561,6 +898,48 @@ impl<'a> Program<'a> {
         }
     }
 
+    /// Changes which images are marked, toggling the current image and the (n-1) next images
+    fn mark(&mut self, action: &MarkAction, times: usize) -> Result<(), String> {
+        match action {
+            MarkAction::Toggle => {
+                if let (Some(index), Some(max_index)) =
+                    (self.paths.index(), self.paths.max_viewable_index())
+                {
+                    let last = cmp::min(index.saturating_add(times.max(1) - 1), max_index);
+                    for i in index..=last {
+                        self.paths.toggle_mark(i);
+                    }
+                }
+            }
+            MarkAction::All => self.paths.mark_all(),
+            MarkAction::Invert => self.paths.invert_marks(),
+            MarkAction::Clear => self.paths.clear_marks(),
+        }
+        self.render_screen(false)
+    }
+
+    /// Stores the current image under a bookmark or jumps to the image stored under one
+    fn bookmark(&mut self, action: &BookmarkAction) -> Result<(), String> {
+        match *action {
+            BookmarkAction::Set(letter) => {
+                self.ui_state.mode = if self.paths.set_bookmark(letter) {
+                    Mode::Success(format!("Set bookmark '{}", letter))
+                } else {
+                    Mode::Error("no image to bookmark".to_string())
+                };
+                self.ui_state.rerender_time = Some(Instant::now());
+            }
+            BookmarkAction::Jump(letter) => match self.paths.bookmark(letter) {
+                Some(index) => self.jump_to_image_index(index)?,
+                None => {
+                    self.ui_state.mode = Mode::Error(format!("No bookmark '{}", letter));
+                    self.ui_state.rerender_time = Some(Instant::now());
+                }
+            },
+        }
+        self.render_screen(false)
+    }
+
     /// Jumps to specific image
     /// Caps at artificial length or last image if index supplied is too large
     fn jump_to_image_index(&mut self, index: usize) -> Result<(), String> {
@@ -588,6 +967,10 @@ impl<'a> Program<'a> {
                 Mode::MultiNormal => {
                     self.run_multi_normal_mode()?;
                 }
+                Mode::Gallery => {
+                    self.run_gallery_mode()?;
+                    self.render_screen(false)?;
+                }
                 Mode::Command(..) => {
                     self.run_command_mode()?;
                     // Force renders in order to remove "Command" and other info from bar
@@ -595,11 +978,15 @@ impl<'a> Program<'a> {
                 }
                 Mode::Error(..) => {
                     self.render_screen(false)?;
-                    self.ui_state.mode = Mode::Normal;
+                    // Thumbnails loading in the gallery would wipe the error before it is read
+                    if self.ui_state.gallery.open {
+                        self.ui_state.rerender_time = Some(Instant::now());
+                    }
+                    self.ui_state.mode = self.input_mode();
                 }
                 Mode::Success(..) => {
                     self.render_screen(false)?;
-                    self.ui_state.mode = Mode::Normal;
+                    self.ui_state.mode = self.input_mode();
                 }
                 Mode::Exit => break 'main_loop,
             }
@@ -696,7 +1083,14 @@ impl<'a> Program<'a> {
                     self.ui_state.mode = Mode::MultiNormal;
                     return Ok(CompleteType::Break);
                 }
+                Action::SwitchGalleryMode => {
+                    self.ui_state.gallery.open = true;
+                    self.ui_state.mode = Mode::Gallery;
+                    self.render_screen(false)?;
+                    return Ok(CompleteType::Break);
+                }
                 Action::ToggleFit => self.toggle_fit()?,
+                Action::Click(x, y) => self.click(x, y)?,
                 Action::FlipHorizontal => self.flip_horizontal()?,
                 Action::FlipVertical => self.flip_vertical()?,
                 Action::CenterImage => self.center_image()?,
@@ -720,6 +1114,52 @@ impl<'a> Program<'a> {
                 Action::Pan(PanAction::Right) => self.pan_right(times)?,
                 Action::Pan(PanAction::Up) => self.pan_up(times)?,
                 Action::Pan(PanAction::Down) => self.pan_down(times)?,
+                // Space pauses the flipbook or slideshow while one is running
+                Action::TogglePlayback => {
+                    if let Some(flipbook) = self.ui_state.flipbook.as_mut() {
+                        flipbook.toggle_pause();
+                        self.render_screen(false)?
+                    } else if let Some(slideshow) = self.ui_state.slideshow.as_mut() {
+                        slideshow.toggle_pause();
+                        self.render_screen(false)?
+                    } else {
+                        self.toggle_playback()?
+                    }
+                }
+                Action::Frame(FrameAction::Next) => self.next_frame(times)?,
+                Action::Frame(FrameAction::Prev) => self.prev_frame(times)?,
+                Action::Page(PageAction::Next) => self.next_page(times)?,
+                Action::Page(PageAction::Prev) => self.prev_page(times)?,
+                Action::Speed(SpeedAction::Faster) => {
+                    self.ui_state.playback.speed_up(times);
+                    self.render_screen(false)?;
+                }
+                Action::Speed(SpeedAction::Slower) => {
+                    self.ui_state.playback.slow_down(times);
+                    self.render_screen(false)?;
+                }
+                Action::Exposure(ToneAction::Increase) => {
+                    self.ui_state.tone.increase_exposure(times);
+                    self.tone_map()?;
+                    self.render_screen(false)?;
+                }
+                Action::Exposure(ToneAction::Decrease) => {
+                    self.ui_state.tone.decrease_exposure(times);
+                    self.tone_map()?;
+                    self.render_screen(false)?;
+                }
+                Action::Gamma(ToneAction::Increase) => {
+                    self.ui_state.tone.increase_gamma(times);
+                    self.tone_map()?;
+                    self.render_screen(false)?;
+                }
+                Action::Gamma(ToneAction::Decrease) => {
+                    self.ui_state.tone.decrease_gamma(times);
+                    self.tone_map()?;
+                    self.render_screen(false)?;
+                }
+                Action::Mark(ref action) => self.mark(action, times)?,
+                Action::Bookmark(ref action) => self.bookmark(action)?,
                 Action::Copy => match self.copy_images(times) {
                     Ok(s) => {
                         self.ui_state.mode = Mode::Success(s);
@@ -772,11 +1212,56 @@ impl<'a> Program<'a> {
         Ok(CompleteType::Complete)
     }
 
+    /// Processes Gallery Mode Actions, the ones shared with normal mode are dispatched as in normal
+    /// mode
+    /// Ok result tells whether to continue or break out of the current Mode
+    fn dispatch_gallery(&mut self, process_action: ProcessAction) -> Result<CompleteType, String> {
+        match process_action.action {
+            Action::Select(ref direction) => self.select(direction, process_action.times)?,
+            Action::SwitchNormalMode => {
+                self.ui_state.gallery.open = false;
+                self.ui_state.mode = Mode::Normal;
+                self.render_screen(false)?;
+                return Ok(CompleteType::Break);
+            }
+            _ => return self.dispatch_normal(process_action),
+        }
+        Ok(CompleteType::Complete)
+    }
+
+    /// run_gallery_mode is the event loop that listens for input and delegates accordingly for
+    /// gallery mode
+    fn run_gallery_mode(&mut self) -> Result<(), String> {
+        loop {
+            for event in self.screen.sdl_context.event_pump()?.poll_iter() {
+                let action = ui::process_gallery_mode(&mut self.ui_state, &event);
+                if let CompleteType::Break = self.dispatch_gallery(action)? {
+                    return Ok(());
+                }
+            }
+
+            if let Some(ts) = self.ui_state.rerender_time {
+                if Instant::now().duration_since(ts) > Duration::from_millis(1500) {
+                    self.ui_state.rerender_time = None;
+                    self.ui_state.mode = Mode::Gallery;
+                    return Ok(());
+                }
+            }
+            // Messages stay up until they time out
+            if self.load_thumbnails() && self.ui_state.rerender_time.is_none() {
+                self.render_screen(false)?;
+            }
+            self.scan()?;
+            std::thread::sleep(Duration::from_millis(1000 / 60));
+        }
+    }
+
     /// run_normal_mode is the event loop that listens for input and delegates accordingly for
     /// normal mode
     fn run_normal_mode(&mut self) -> Result<(), String> {
         'mainloop: loop {
             for event in self.screen.sdl_context.event_pump()?.poll_iter() {
+                let index = self.paths.index();
                 let action = ui::process_normal_mode(&mut self.ui_state, &event);
                 self.ui_state.process_action(action.clone());
                 let next_step = self.dispatch_normal(action.clone())?;
@@ -806,6 +1291,13 @@ impl<'a> Program<'a> {
                         }
                     }
                 }
+
+                // Navigating by hand gives the new image the whole interval of the slideshow
+                if self.paths.index() != index {
+                    if let Some(slideshow) = self.ui_state.slideshow.as_mut() {
+                        slideshow.restart();
+                    }
+                }
             }
 
             if let Some(ts) = self.ui_state.rerender_time {
@@ -815,7 +1307,22 @@ impl<'a> Program<'a> {
                     return Ok(());
                 }
             }
-            std::thread::sleep(Duration::from_millis(1000 / 60));
+            if self.ui_state.render_filmstrip && self.load_thumbnails() {
+                self.render_screen(false)?;
+            }
+            self.animate()?;
+            self.advance_slideshow()?;
+            self.play_flipbook()?;
+            self.scan()?;
+            // Wake up in time for the next frame of the flipbook
+            let wait = Duration::from_millis(1000 / 60);
+            let wait = match self.ui_state.flipbook.as_ref() {
+                Some(flipbook) => flipbook
+                    .until_due(Instant::now())
+                    .map_or(wait, |due| due.min(wait)),
+                None => wait,
+            };
+            std::thread::sleep(wait);
         }
         Ok(())
     }
@@ -833,6 +1340,21 @@ fn make_dst(tq: &TextureQuery, vp: &Rect, scale: f32, pan_x: f32, pan_y: f32) ->
     Rect::new(x, y, width, height)
 }
 
+/// fit scale is the scale that fits an image of src_x by src_y pixels in a window of dst_x by dst_y
+/// pixels, 1.0 for images smaller than the window
+fn fit_scale(src_x: f32, src_y: f32, dst_x: f32, dst_y: f32) -> f32 {
+    // case 1: both source dimensions smaller
+    if src_x < dst_x && src_y < dst_y {
+        return 1.0;
+    }
+    // case 2: source aspect ratio is larger
+    if src_x / src_y > dst_x / dst_y {
+        return dst_x / src_x;
+    }
+    // case 3: source aspect ratio is smaller
+    dst_y / src_y
+}
+
 /// Compute increment of skips
 /// Does not account for overflow or underflow of vector
 fn compute_skip_size(images: &[PathBuf]) -> usize {
@@ -1,15 +1,27 @@
+use crate::cache::{Key, TextureCache};
+use crate::decode::{self, Frame, Image};
 use crate::infobar;
-use crate::program::{make_dst, Program};
-use crate::ui::{HelpRender, Mode, RotAngle};
-use sdl2::image::LoadTexture;
-use sdl2::pixels::Color;
+use crate::paths::Paths;
+use crate::program::{fit_scale, make_dst, Program};
+use crate::screen::{Cached, Screen, Shown};
+use crate::tiles::{self, Tile, Tiles};
+use crate::ui::{ColorProfile, HelpRender, Mode, RotAngle};
+use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::Rect;
-use sdl2::render::BlendMode;
+use sdl2::render::{BlendMode, WindowCanvas};
 
 const PADDING: i32 = 30;
 const HALF_PAD: i32 = 15;
 const LINE_HEIGHT: i32 = 22;
 const LINE_PADDING: i32 = 5;
+/// Still images fitting the window at a scale below this are downscaled before they are uploaded
+const DOWNSCALE_BELOW: f32 = 0.5;
+/// Width in pixels of the frame around the selected thumbnail
+const SELECTION_BORDER: i32 = 4;
+/// Side of the square drawn in the corner of the thumbnails of marked images
+const MARK_SIZE: u32 = 12;
+/// Most characters of a path shown in the list of bookmarks, longer paths are cut at the start
+const BOOKMARK_PATH_WIDTH: usize = 60;
 
 struct Colors {
     primary: Color,
@@ -26,7 +38,14 @@ impl<'a> Program<'a> {
             return self.render_blank();
         }
         self.screen.canvas.clear();
-        self.render_image(force_render)?;
+        if self.ui_state.gallery.open {
+            self.render_gallery()?;
+        } else {
+            self.render_image(force_render)?;
+            if self.ui_state.render_filmstrip {
+                self.render_filmstrip();
+            }
+        }
         if self.ui_state.render_infobar {
             self.render_infobar()?;
         }
@@ -39,6 +58,8 @@ impl<'a> Program<'a> {
 
     fn render_image(&mut self, force_render: bool) -> Result<(), String> {
         self.set_image_texture(force_render)?;
+        self.rasterize_vector()?;
+        self.restore_resolution()?;
         match self.screen.last_texture {
             Some(_) => (),
             None => return Ok(()),
@@ -50,7 +71,7 @@ impl<'a> Program<'a> {
         let dst = make_dst(
             &query,
             &target,
-            self.ui_state.scale,
+            self.ui_state.scale / self.screen.texture_scale,
             self.ui_state.pan_x,
             self.ui_state.pan_y,
         );
@@ -61,12 +82,10 @@ impl<'a> Program<'a> {
             RotAngle::Down => 180.0,
             RotAngle::Left => 270.0,
         };
-        if let Err(e) = self.screen.canvas.copy_ex(
-            &tex,
-            None,
+        if let Err(e) = tex.draw(
+            &mut self.screen.canvas,
             dst,
             angle,
-            None,
             self.ui_state.flip_horizontal,
             self.ui_state.flip_vertical,
         ) {
@@ -75,6 +94,89 @@ impl<'a> Program<'a> {
         Ok(())
     }
 
+    /// Renders the images as a grid of thumbnails with the current image selected, queueing the
+    /// thumbnails in view and on the next screen that are not loaded yet
+    fn render_gallery(&mut self) -> Result<(), String> {
+        let (index, len) = match (self.paths.index(), self.paths.max_viewable()) {
+            (Some(index), Some(len)) => (index, len),
+            _ => return Ok(()),
+        };
+        let grid = self.gallery_grid();
+        let top_row = grid.scroll(self.ui_state.gallery.top_row, index);
+        self.ui_state.gallery.top_row = top_row;
+
+        let images = self.paths.images();
+        let canvas = &mut self.screen.canvas;
+        let thumbnails = &mut self.screen.thumbnails;
+        let mut wanted = Vec::new();
+        for i in grid.visible(top_row, len) {
+            let thumbnail = thumbnails.get(&Key::new(&images[i], 0));
+            let cell = grid.cell(top_row, i);
+            let marked = self.paths.is_marked(i);
+            if !draw_thumbnail(canvas, thumbnail, cell, i == index, marked) {
+                wanted.push(images[i].clone());
+            }
+        }
+        // Decode the next screen ahead so scrolling down shows thumbnails right away
+        for path in &images[grid.visible(top_row + grid.rows, len)] {
+            if thumbnails.get(&Key::new(path, 0)).is_none() {
+                wanted.push(path.clone());
+            }
+        }
+        self.thumbnailer.request(&wanted);
+        Ok(())
+    }
+
+    /// Renders the strip of thumbnails around the current image over the bottom of the image,
+    /// queueing the thumbnails that are not loaded yet
+    fn render_filmstrip(&mut self) {
+        let (index, len) = match (self.paths.index(), self.paths.max_viewable()) {
+            (Some(index), Some(len)) => (index, len),
+            _ => return,
+        };
+        let strip = self.filmstrip();
+        let images = self.paths.images();
+        let canvas = &mut self.screen.canvas;
+        let thumbnails = &mut self.screen.thumbnails;
+        canvas.set_draw_color(help_background_color());
+        canvas.set_blend_mode(BlendMode::Blend);
+        if let Err(e) = canvas.fill_rect(strip.area()) {
+            eprintln!("Failed to draw filmstrip {}", e);
+        }
+        let mut wanted = Vec::new();
+        for (i, cell) in strip.cells(index, len) {
+            let thumbnail = thumbnails.get(&Key::new(&images[i], 0));
+            if !draw_thumbnail(canvas, thumbnail, cell, i == index, self.paths.is_marked(i)) {
+                wanted.push(images[i].clone());
+            }
+        }
+        self.thumbnailer.request(&wanted);
+    }
+
+    /// Uploads the thumbnails decoded in the background into the thumbnail cache, returning
+    /// whether any arrived
+    pub(super) fn load_thumbnails(&mut self) -> bool {
+        let finished = self.thumbnailer.finished();
+        let arrived = !finished.is_empty();
+        for (path, thumbnail) in finished {
+            let (thumbnail, size) = match thumbnail.and_then(|frame| {
+                let size = frame.pixels.len();
+                self.create_frame_texture(&frame)
+                    .map(|texture| (texture, size))
+            }) {
+                Ok((texture, size)) => (Some(texture), size),
+                Err(e) => {
+                    eprintln!("Failed to load thumbnail of {}: {}", path.display(), e);
+                    (None, 0)
+                }
+            };
+            self.screen
+                .thumbnails
+                .insert(Key::new(&path, 0), thumbnail, size);
+        }
+        arrived
+    }
+
     /// Renders the image at the current index
     fn set_image_texture(&mut self, force_render: bool) -> Result<(), String> {
         if self.paths.index() == self.screen.last_index
@@ -85,37 +187,285 @@ impl<'a> Program<'a> {
             return Ok(());
         }
 
-        //reset horizontal and vertical flip on new image load
-        self.ui_state.flip_horizontal = false;
-        self.ui_state.flip_vertical = false;
-
-        let current_imagepath = match self.paths.current_image_path() {
-            Some(path) => path,
+        let key = match self.paths.current_image_path() {
+            Some(path) => Key::new(path, self.paths.page()),
             // No images were found, so no image to load
             None => return Ok(()),
         };
+        if self.screen.failed.as_ref() == Some(&key) {
+            return Ok(());
+        }
 
-        let texture = match self.screen.texture_creator.load_texture(current_imagepath) {
-            Ok(t) => {
-                self.screen.last_index = self.paths.index();
-                t
-            }
-            Err(e) => {
-                eprintln!("Failed to render image {}", e);
-                return Ok(());
-            }
+        // The image shown is rendered again, for example after :autorotate, keeping its texture
+        let shown_again = self
+            .screen
+            .shown
+            .as_ref()
+            .is_some_and(|shown| shown.key == key);
+        let shown = if shown_again {
+            self.take_shown().map(|(_, cached)| cached)
+        } else {
+            None
+        };
+        let (cached, hit) = match shown.or_else(|| self.screen.cache.take(&key)) {
+            Some(cached) => (cached, true),
+            None => match self.load_texture(&key) {
+                Ok(cached) => (cached, false),
+                // Keep showing the previous image
+                Err(e) => {
+                    eprintln!("Failed to render image {}", e);
+                    self.screen.failed = Some(key);
+                    return Ok(());
+                }
+            },
         };
+        // The displayed texture is only given up once there is one to replace it
+        self.cache_texture();
+        self.screen.failed = None;
+        if hit {
+            self.prefetch();
+        }
 
+        self.screen.last_index = self.paths.index();
+        self.paths.set_page_count(cached.pages);
+        self.ui_state.color_profile = cached.color_profile;
+        self.ui_state.tone.hdr = cached.image.as_ref().is_some_and(|i| i.hdr.is_some());
+        // Start playback from the first frame
+        match cached.image.as_ref() {
+            Some(image) => self
+                .ui_state
+                .playback
+                .reset(image.frames.len(), image.plays),
+            None => self.ui_state.playback.reset(1, Some(1)),
+        }
         // Set the default state for viewing of the image
-        self.screen.last_texture = Some(texture);
+        self.screen.last_image = cached.image;
+        self.screen.last_texture = Some(cached.texture);
+        self.screen.texture_scale = cached.texture_scale;
+        self.screen.shown = Some(Shown {
+            key,
+            pages: cached.pages,
+            orientation: cached.orientation,
+        });
         self.screen.dirty = false;
+        if hit {
+            // The texture holds the frame and tone it was last displayed with
+            if self.ui_state.tone.hdr {
+                self.tone_map()?;
+            } else {
+                self.update_frame_texture()?;
+            }
+        }
+        // upright from the EXIF orientation, otherwise 0 degree rotation and no flips
+        if self.ui_state.auto_rotate {
+            self.ui_state.orient(cached.orientation);
+        } else {
+            self.ui_state.orient(1);
+        }
         // fit to screen
         self.ui_state.scale = self.calculate_scale_for_fit();
         // no offsets
         self.ui_state.pan_x = 0.0;
         self.ui_state.pan_y = 0.0;
-        // 0 degree rotation
-        self.ui_state.rot_angle = RotAngle::Up;
+        Ok(())
+    }
+
+    /// Decodes the image of key and uploads it into a new texture
+    fn load_texture(&mut self, key: &Key) -> Result<Cached<'a>, String> {
+        // Only the upload is left for images decoded in the background
+        let image = match key.page {
+            0 => self.prefetcher.take(&key.path),
+            _ => None,
+        };
+        self.prefetch();
+        let image = image.unwrap_or_else(|| decode::open(&key.path, key.page))?;
+        self.upload(image, false)
+    }
+
+    /// Uploads a decoded image into a new texture. Unless full_resolution is set, still images
+    /// far larger than the window are downscaled to about the size they fit the window at
+    fn upload(&self, mut image: Image, full_resolution: bool) -> Result<Cached<'a>, String> {
+        // Map high dynamic range pixels at the exposure and gamma picked for earlier images
+        if let Some(hdr) = image.hdr.as_ref() {
+            image.frames[0].pixels =
+                hdr.tone_map(self.ui_state.tone.exposure, self.ui_state.tone.gamma);
+        }
+        // Convert colors before the pixels are uploaded when color management is on
+        let color_profile = if image.icc_profile.is_none() {
+            ColorProfile::Untagged
+        } else if let Some(true) = self.color_manager.as_ref().map(|m| m.convert(&mut image)) {
+            ColorProfile::Converted
+        } else {
+            ColorProfile::Tagged
+        };
+        // Only animated, vector and high dynamic range images need to be kept around after the upload
+        let keep = image.is_animated() || image.vector.is_some() || image.hdr.is_some();
+        let mut texture_scale = 1.0;
+        if !full_resolution && !keep {
+            let frame = &image.frames[0];
+            let viewport = self.screen.canvas.viewport();
+            let (width, height) = (frame.width as f32, frame.height as f32);
+            let (vp_width, vp_height) = (viewport.width() as f32, viewport.height() as f32);
+            // Large enough to fit the window however the image ends up rotated
+            let fit = fit_scale(width, height, vp_width, vp_height)
+                .max(fit_scale(height, width, vp_width, vp_height));
+            if fit < DOWNSCALE_BELOW {
+                let new_width = (width * fit).round().max(1.0) as u32;
+                let new_height = (height * fit).round().max(1.0) as u32;
+                if let Some(resized) = frame.resized(new_width, new_height) {
+                    texture_scale = new_width as f32 / width;
+                    image.frames[0] = resized;
+                }
+            }
+        }
+        let texture = self.create_frame_texture(&image.frames[0])?;
+        let pages = image.pages;
+        let orientation = image.orientation;
+        let image = if keep { Some(image) } else { None };
+        Ok(Cached {
+            texture,
+            image,
+            texture_scale,
+            pages,
+            orientation,
+            color_profile,
+        })
+    }
+
+    /// Takes the displayed texture along with the key of its image
+    fn take_shown(&mut self) -> Option<(Key, Cached<'a>)> {
+        let texture = self.screen.last_texture.take()?;
+        let image = self.screen.last_image.take();
+        // Textures of images that were moved or deleted are dropped
+        let shown = self.screen.shown.take()?;
+        let cached = Cached {
+            texture,
+            image,
+            texture_scale: self.screen.texture_scale,
+            pages: shown.pages,
+            orientation: shown.orientation,
+            color_profile: self.ui_state.color_profile,
+        };
+        Some((shown.key, cached))
+    }
+
+    /// Moves the displayed texture into the cache, so going back to its image needs no decoding
+    fn cache_texture(&mut self) {
+        if let Some((key, cached)) = self.take_shown() {
+            let query = cached.texture.query();
+            let size = query.width as usize * query.height as usize * 4
+                + cached.image.as_ref().map_or(0, Image::memory_size);
+            self.screen.cache.insert(key, cached, size);
+        }
+    }
+
+    /// Loads a downscaled image again at full resolution once it is zoomed in past the scale it
+    /// was downscaled to
+    fn restore_resolution(&mut self) -> Result<(), String> {
+        // Only still images are downscaled, the rest are kept decoded
+        if self.screen.last_image.is_some()
+            || !needs_full_resolution(self.screen.texture_scale, self.ui_state.scale)
+        {
+            return Ok(());
+        }
+        let key = match self.screen.shown.as_ref() {
+            Some(shown) => shown.key.clone(),
+            None => return Ok(()),
+        };
+        match decode::open(&key.path, key.page).and_then(|image| self.upload(image, true)) {
+            Ok(cached) => {
+                self.screen.last_texture = Some(cached.texture);
+                self.screen.texture_scale = cached.texture_scale;
+            }
+            Err(e) => eprintln!("Failed to render image {}", e),
+        }
+        Ok(())
+    }
+
+    /// Creates the textures holding the pixels of a decoded frame, split into tiles when the frame
+    /// is larger than the renderer allows a texture to be
+    fn create_frame_texture(&self, frame: &Frame) -> Result<Tiles<'a>, String> {
+        let info = self.screen.canvas.info();
+        // Renderers without a limit report 0
+        let max = [info.max_texture_width, info.max_texture_height]
+            .iter()
+            .filter(|&&max| max > 0)
+            .min()
+            .copied()
+            .unwrap_or(u32::MAX);
+        let mut tiles = Vec::new();
+        for area in tiles::grid(frame.width, frame.height, max) {
+            let mut texture = self
+                .screen
+                .texture_creator
+                .create_texture_streaming(PixelFormatEnum::RGBA32, area.width(), area.height())
+                .map_err(|e| e.to_string())?;
+            texture.set_blend_mode(BlendMode::Blend);
+            tiles.push(Tile { texture, area });
+        }
+        let mut tiles = Tiles::new(tiles, frame.width, frame.height);
+        tiles.update(frame)?;
+        Ok(tiles)
+    }
+
+    /// Rasterizes a vector image again when the current scale differs from the scale its texture
+    /// was rasterized at, so zooming keeps it sharp
+    fn rasterize_vector(&mut self) -> Result<(), String> {
+        let svg = match self.screen.last_image.as_ref() {
+            Some(image) => match image.vector.as_ref() {
+                Some(svg) => svg,
+                None => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+        let scale = svg.raster_scale(self.ui_state.scale);
+        if (scale - self.screen.texture_scale).abs() < 0.001 {
+            return Ok(());
+        }
+        let texture = match svg
+            .rasterize(scale)
+            .and_then(|frame| self.create_frame_texture(&frame))
+        {
+            Ok(texture) => texture,
+            Err(e) => {
+                eprintln!("Failed to render image {}", e);
+                return Ok(());
+            }
+        };
+        self.screen.last_texture = Some(texture);
+        self.screen.texture_scale = scale;
+        Ok(())
+    }
+
+    /// Maps the float pixels of a high dynamic range image to 8 bits again at the current exposure
+    /// and gamma, then uploads them into the last texture
+    pub(super) fn tone_map(&mut self) -> Result<(), String> {
+        let image = match self.screen.last_image.as_mut() {
+            Some(image) => image,
+            None => return Ok(()),
+        };
+        let pixels = match image.hdr.as_ref() {
+            Some(hdr) => hdr.tone_map(self.ui_state.tone.exposure, self.ui_state.tone.gamma),
+            None => return Ok(()),
+        };
+        image.frames[0].pixels = pixels;
+        if self.ui_state.color_profile == ColorProfile::Converted {
+            if let Some(manager) = self.color_manager.as_ref() {
+                manager.convert(image);
+            }
+        }
+        self.update_frame_texture()
+    }
+
+    /// Uploads the current playback frame into the last texture
+    pub(super) fn update_frame_texture(&mut self) -> Result<(), String> {
+        let frame = match self.screen.last_image.as_ref() {
+            Some(image) => &image.frames[self.ui_state.playback.frame],
+            None => return Ok(()),
+        };
+        if let Some(texture) = self.screen.last_texture.as_mut() {
+            texture.update(frame)?;
+        }
         Ok(())
     }
 
@@ -193,10 +543,19 @@ impl<'a> Program<'a> {
     }
 
     fn render_help(&mut self) -> Result<(), String> {
+        let lines;
         let text = match self.ui_state.render_help {
             HelpRender::None => return Ok(()),
             HelpRender::Normal => normal_help_text(),
             HelpRender::Command => command_help_text(),
+            HelpRender::Bookmarks => {
+                lines = bookmark_text(&self.paths);
+                lines.iter().map(String::as_str).collect()
+            }
+            HelpRender::Debug => {
+                lines = debug_text(&self.screen);
+                lines.iter().map(String::as_str).collect()
+            }
         };
         let total_height = LINE_HEIGHT * text.len() as i32 + LINE_PADDING * (text.len() as i32 - 1);
         let mut y = (self.screen.canvas.viewport().height() as f32 / 2.0
@@ -298,9 +657,98 @@ impl<'a> Program<'a> {
     }
 }
 
+/// Whether a texture downscaled to texture_scale has to be loaded again at full resolution to be
+/// shown at scale, textures at full resolution never are
+fn needs_full_resolution(texture_scale: f32, scale: f32) -> bool {
+    texture_scale < 1.0 && scale > texture_scale + 0.001
+}
+
+/// Draws a thumbnail scaled down to fit cell, a placeholder while it is not loaded and a crossed
+/// out one when it failed to load, with a square in the corner when the image is marked. Returns
+/// whether the thumbnail was loaded or failed to load
+fn draw_thumbnail(
+    canvas: &mut WindowCanvas,
+    thumbnail: Option<&Option<Tiles<'_>>>,
+    cell: Rect,
+    selected: bool,
+    marked: bool,
+) -> bool {
+    if selected {
+        let border = Rect::new(
+            cell.x() - SELECTION_BORDER,
+            cell.y() - SELECTION_BORDER,
+            cell.width() + SELECTION_BORDER as u32 * 2,
+            cell.height() + SELECTION_BORDER as u32 * 2,
+        );
+        canvas.set_draw_color(light_blue());
+        if let Err(e) = canvas.fill_rect(border) {
+            eprintln!("Failed to draw selection {}", e);
+        }
+    }
+    canvas.set_draw_color(grey());
+    let loaded = match thumbnail {
+        Some(Some(thumbnail)) => {
+            let query = thumbnail.query();
+            let scale = fit_scale(
+                query.width as f32,
+                query.height as f32,
+                cell.width() as f32,
+                cell.height() as f32,
+            );
+            let dst = Rect::from_center(
+                cell.center(),
+                ((query.width as f32 * scale) as u32).max(1),
+                ((query.height as f32 * scale) as u32).max(1),
+            );
+            if !selected {
+                if let Err(e) = canvas.fill_rect(cell) {
+                    eprintln!("Failed to draw thumbnail {}", e);
+                }
+            }
+            if let Err(e) = thumbnail.draw(canvas, dst, 0.0, false, false) {
+                eprintln!("Failed to copy thumbnail to screen {}", e);
+            }
+            true
+        }
+        // Images that failed to decode are crossed out
+        Some(None) => {
+            if let Err(e) = canvas.fill_rect(cell) {
+                eprintln!("Failed to draw thumbnail {}", e);
+            }
+            canvas.set_draw_color(light_red());
+            let crossed = canvas
+                .draw_line(cell.top_left(), cell.bottom_right())
+                .and_then(|_| canvas.draw_line(cell.top_right(), cell.bottom_left()));
+            if let Err(e) = crossed {
+                eprintln!("Failed to draw thumbnail {}", e);
+            }
+            true
+        }
+        None => {
+            if let Err(e) = canvas.fill_rect(cell) {
+                eprintln!("Failed to draw thumbnail {}", e);
+            }
+            false
+        }
+    };
+    if marked {
+        let mark = Rect::new(
+            cell.right() - MARK_SIZE as i32,
+            cell.y(),
+            MARK_SIZE,
+            MARK_SIZE,
+        );
+        canvas.set_draw_color(light_green());
+        if let Err(e) = canvas.fill_rect(mark) {
+            eprintln!("Failed to draw mark {}", e);
+        }
+    }
+    loaded
+}
+
 fn mode_colors(m: &Mode) -> Colors {
     match m {
-        Mode::Normal | Mode::MultiNormal => Colors {
+        Mode::Normal | Mode::MultiNormal | Mode::Gallery => Colors {
             primary: light_blue(),
             secondary: blue(),
             tertiary: grey(),
@@ -330,9 +778,12 @@ fn mode_colors(m: &Mode) -> Colors {
 
 fn mode_text_color(m: &Mode) -> Color {
     match m {
-        Mode::Normal | Mode::MultiNormal | Mode::Exit | Mode::Command(_) | Mode::Success(_) => {
-            dark_text_color()
-        }
+        Mode::Normal
+        | Mode::MultiNormal
+        | Mode::Gallery
+        | Mode::Exit
+        | Mode::Command(_)
+        | Mode::Success(_) => dark_text_color(),
         Mode::Error(_) => light_text_color(),
     }
 }
@@ -408,12 +859,25 @@ fn normal_help_text() -> Vec<&'static str> {
         "| c          |                            | Copy image to destination folder (default ./keep)   |",
         "| d          | Delete                     | Move image to OS specific trash location            |",
         "| D          | Shift + Delete             | Delete image from its location                      |",
+        "| x          |                            | Toggle mark, X inverts all marks                    |",
+        "| a          |                            | Mark all images                                     |",
+        "| u          |                            | Clear all marks                                     |",
+        "| M a-z      |                            | Bookmark image under a letter                       |",
+        "| ' a-z      |                            | Jump to bookmarked image, :bookmarks lists them     |",
         "| t          |                            | Toggle information bar                              |",
+        "| F          | Left Click on thumbnail    | Toggle filmstrip, click a thumbnail to jump to it   |",
         "| f          | F11                        | Toggle fullscreen mode                              |",
         "| ?          |                            | Toggle help box                                     |",
         "| z          | Left Click                 | Toggle actual size vs scaled image                  |",
         "| Z          |                            | Center image                                        |",
         "| . (period) |                            | Repeat last action                                  |",
+        "| Space      |                            | Pause/Resume flipbook, slideshow or animation       |",
+        "| [/]        |                            | Previous/Next frame of animation                    |",
+        "| {/}        |                            | Slow down/Speed up animation                        |",
+        "| n/p        |                            | Next/Previous page of multi-page image              |",
+        "| e/E        |                            | Raise/Lower exposure of HDR image                   |",
+        "| y/Y        |                            | Raise/Lower gamma of HDR image                      |",
+        "| Tab        | Enter/Esc                  | Toggle gallery of thumbnails, arrows move selection |",
         "+------------+----------------------------+-----------------------------------------------------+",
     ]
 }
@@ -429,6 +893,12 @@ fn command_help_text() -> Vec<&'static str> {
         "|       | sort       | Optional | The method to sort by               |",
         "| df    | destfolder | Required | New folder to move/copy images to   |",
         "| m     | max        | Required | New maximum number of files to view |",
+        "| ar    | autorotate | Optional | Toggle EXIF auto rotation, on/off   |",
+        "| ex    | export     | Optional | Save the image to a file or folder  |",
+        "| ss    | slideshow  | Optional | Start/stop slideshow, seconds/loop  |",
+        "| fb    | flipbook   | Optional | Play images at 24/30/60 fps         |",
+        "| bm    | bookmarks  | None     | Toggle list of bookmarks            |",
+        "| dbg   | debug      | None     | Toggle cache hit/miss statistics    |",
         "+---------------------------------------------------------------------+",
         "| Sorting Options                                                     |",
         "+---------------------------------------------------------------------+",
@@ -440,3 +910,102 @@ fn command_help_text() -> Vec<&'static str> {
         "+---------------------------------------------------------------------+",
     ]
 }
+
+/// Table of the bookmarks listing the letter, position and path of each bookmarked image
+fn bookmark_text(paths: &Paths) -> Vec<String> {
+    let rows: Vec<(String, String, String)> = paths
+        .bookmarks()
+        .into_iter()
+        .map(|(letter, index)| {
+            let path = paths.images()[index].display().to_string();
+            let len = path.chars().count();
+            let path = if len > BOOKMARK_PATH_WIDTH {
+                let tail: String = path.chars().skip(len - BOOKMARK_PATH_WIDTH + 3).collect();
+                format!("...{}", tail)
+            } else {
+                path
+            };
+            (format!("'{}", letter), (index + 1).to_string(), path)
+        })
+        .collect();
+    if rows.is_empty() {
+        let line = "| No bookmarks, press M and a letter to set one |";
+        let border = format!("+{}+", "-".repeat(line.len() - 2));
+        return vec![border.clone(), line.to_string(), border];
+    }
+    let image_width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0).max(5);
+    let path_width = rows
+        .iter()
+        .map(|row| row.2.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+    let border = format!(
+        "+-----+-{}-+-{}-+",
+        "-".repeat(image_width),
+        "-".repeat(path_width)
+    );
+    let row = |key: &str, image: &str, path: &str| {
+        format!(
+            "| {:<3} | {:<image_width$} | {:<path_width$} |",
+            key,
+            image,
+            path,
+            image_width = image_width,
+            path_width = path_width
+        )
+    };
+    let mut text = vec![border.clone(), row("Key", "Image", "Path"), border.clone()];
+    text.extend(rows.iter().map(|(key, image, path)| row(key, image, path)));
+    text.push(border);
+    text
+}
+
+/// Table of the entries, memory use, hits and misses of the texture caches
+fn debug_text(screen: &Screen<'_>) -> Vec<String> {
+    let border = "+------------+---------+---------------+----------+----------+".to_string();
+    let header = format!(
+        "| {:<10} | {:<7} | {:<13} | {:<8} | {:<8} |",
+        "Cache", "Entries", "Memory", "Hits", "Misses"
+    );
+    vec![
+        border.clone(),
+        header,
+        border.clone(),
+        cache_row("Images", &screen.cache),
+        cache_row("Thumbnails", &screen.thumbnails),
+        border,
+    ]
+}
+
+/// Row of the debug table for cache
+fn cache_row<T>(name: &str, cache: &TextureCache<T>) -> String {
+    const MEGABYTE: usize = 1024 * 1024;
+    let memory = format!(
+        "{}/{} MB",
+        cache.used() / MEGABYTE,
+        cache.budget() / MEGABYTE
+    );
+    format!(
+        "| {:<10} | {:>7} | {:>13} | {:>8} | {:>8} |",
+        name,
+        cache.len(),
+        memory,
+        cache.hits(),
+        cache.misses()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::needs_full_resolution;
+
+    #[test]
+    fn test_full_resolution_only_reloads_downscaled_textures() {
+        assert!(!needs_full_resolution(0.25, 0.2));
+        assert!(!needs_full_resolution(0.25, 0.25));
+        assert!(needs_full_resolution(0.25, 0.3));
+        // Zooming past 100% never reloads a texture that is already at full resolution
+        assert!(!needs_full_resolution(1.0, 4.0));
+    }
+}
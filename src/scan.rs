@@ -0,0 +1,143 @@
+//! # Scan
+//!
+//! Globs for images on a background thread, so the first image is displayed before a walk through
+//! a large folder tree finishes. Images are handed over in batches as they are found, in the order
+//! the glob finds them.
+
+use crate::formats::{FormatFilter, Skipped};
+use glob::glob;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long the scanning thread gathers images before handing them over
+const BATCH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Batch is the images found since the last batch along with the files skipped so far
+struct Batch {
+    images: Vec<PathBuf>,
+    skipped: Skipped,
+}
+
+/// Scanner receives the images found by a glob running on a background thread
+pub struct Scanner {
+    receiver: Receiver<Batch>,
+    /// number of images received so far
+    found: usize,
+    /// files skipped so far
+    skipped: Skipped,
+    /// whether the glob finished and every image was received
+    done: bool,
+}
+
+impl Scanner {
+    /// Starts globbing pattern in the background, keeping the images in a format formats accepts.
+    /// Errors when pattern is not a valid glob
+    pub fn start(pattern: &Path, formats: FormatFilter) -> Result<Self, String> {
+        let paths = glob(&pattern.to_string_lossy()).map_err(|e| e.to_string())?;
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            let mut batch = Batch {
+                images: Vec::new(),
+                skipped: Skipped::default(),
+            };
+            let mut last_sent = Instant::now();
+            for path in paths {
+                match path {
+                    Ok(path) => formats.push(&mut batch.images, &mut batch.skipped, path),
+                    Err(e) => eprintln!("Path not processable {}", e),
+                }
+                if last_sent.elapsed() < BATCH_INTERVAL {
+                    continue;
+                }
+                let next = Batch {
+                    images: Vec::new(),
+                    skipped: batch.skipped.clone(),
+                };
+                // Stop once the scanner is dropped
+                if sender.send(batch).is_err() {
+                    return;
+                }
+                batch = next;
+                last_sent = Instant::now();
+            }
+            let _ = sender.send(batch);
+        });
+        Ok(Scanner {
+            receiver,
+            found: 0,
+            skipped: Skipped::default(),
+            done: false,
+        })
+    }
+
+    /// Takes the images found since the last poll without waiting for more
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let mut images = Vec::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(batch) => {
+                    images.extend(batch.images);
+                    self.skipped = batch.skipped;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+        self.found += images.len();
+        images
+    }
+
+    /// Number of images found so far
+    pub fn found(&self) -> usize {
+        self.found
+    }
+
+    /// Files skipped so far
+    pub fn skipped(&self) -> &Skipped {
+        &self.skipped
+    }
+
+    /// Whether the glob finished and every image it found was polled
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scanner;
+    use crate::formats::FormatFilter;
+    use std::path::Path;
+    use std::str::FromStr;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_scanner_finds_every_image() {
+        let formats = FormatFilter::from_str("-gif").unwrap();
+        let mut scanner = Scanner::start(Path::new("tests/fixtures/*"), formats).unwrap();
+        let mut images = Vec::new();
+        for _ in 0..1000 {
+            images.extend(scanner.poll());
+            if scanner.is_done() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(scanner.is_done());
+        assert_eq!(scanner.found(), images.len());
+        assert!(images.iter().any(|path| path.ends_with("rgb.ppm")));
+        assert!(!images.iter().any(|path| path.ends_with("still.gif")));
+        assert_eq!(scanner.skipped().excluded, 1);
+    }
+
+    #[test]
+    fn test_scanner_rejects_invalid_glob() {
+        assert!(Scanner::start(Path::new("[a"), FormatFilter::default()).is_err());
+    }
+}
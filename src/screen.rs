@@ -1,9 +1,14 @@
 //! Screen contains the Screen struct which contains all SDL initialised data required
 //! for building the window and rendering to screen.
+use crate::cache::{Key, TextureCache};
+use crate::decode::Image;
+use crate::tiles::Tiles;
+use crate::ui::ColorProfile;
 use sdl2::render::{TextureCreator, WindowCanvas};
 use sdl2::ttf::Font;
 use sdl2::video::{FullscreenType, WindowContext};
 use sdl2::Sdl;
+use std::path::Path;
 use FullscreenType::*;
 
 /// Screen contains all SDL related data required for running the screen rendering.
@@ -20,13 +25,71 @@ pub struct Screen<'a> {
     pub mono_font: Font<'a, 'static>,
     /// last_index is the index of the last texture rendered
     pub last_index: Option<usize>,
-    /// last_texture is the last image texture rendered
-    pub last_texture: Option<sdl2::render::Texture<'a>>,
-    /// dirty, if true indicates that last texture must be discarded
+    /// last_texture holds the tiles of the last image rendered
+    pub last_texture: Option<Tiles<'a>>,
+    /// last_image holds the decoded last image rendered when it is animated, a vector image or
+    /// high dynamic range
+    pub last_image: Option<Image>,
+    /// texture_scale is the scale last_texture was rasterized at, 1.0 for anything but vector
+    /// images
+    pub texture_scale: f32,
+    /// shown describes the image of last_texture, None once that image was moved or deleted
+    pub shown: Option<Shown>,
+    /// cache holds the textures of recently rendered images, last_texture moves into it when
+    /// another image is rendered
+    pub cache: TextureCache<Cached<'a>>,
+    /// thumbnails holds the thumbnails of the gallery, None for images that failed to decode
+    pub thumbnails: TextureCache<Option<Tiles<'a>>>,
+    /// dirty, if true indicates that the image must be loaded again, from the cache when it is
+    /// still there
     pub dirty: bool,
+    /// failed is the key of the last image that failed to load, it is not decoded again on every
+    /// render until another image was loaded
+    pub failed: Option<Key>,
+}
+
+/// Shown describes the image whose texture is rendered
+pub struct Shown {
+    /// path, modification time and page of the image
+    pub key: Key,
+    /// number of pages in the image
+    pub pages: usize,
+    /// EXIF orientation of the image
+    pub orientation: u32,
+}
+
+/// Cached is the texture of an image that is not rendered, along with everything needed to render
+/// it again without decoding it
+pub struct Cached<'a> {
+    /// tiles holding the pixels of the image
+    pub texture: Tiles<'a>,
+    /// the decoded image when it is animated, a vector image or high dynamic range
+    pub image: Option<Image>,
+    /// scale texture was rasterized at
+    pub texture_scale: f32,
+    /// number of pages in the image
+    pub pages: usize,
+    /// EXIF orientation of the image
+    pub orientation: u32,
+    /// whether the image has an embedded color profile and if it was converted
+    pub color_profile: ColorProfile,
 }
 
 impl Screen<'_> {
+    /// Drops the textures of every page of the image at path, for images that were moved or
+    /// deleted
+    pub fn forget(&mut self, path: &Path) {
+        self.cache.remove_path(path);
+        self.thumbnails.remove_path(path);
+        if self
+            .shown
+            .as_ref()
+            .is_some_and(|shown| shown.key.path == path)
+        {
+            self.shown = None;
+        }
+    }
+
     /// Updates window for fullscreen state
     pub fn update_fullscreen(&mut self, fullscreen: bool) -> Result<(), String> {
         let fullscreen_type = if fullscreen { Off } else { True };
@@ -0,0 +1,215 @@
+//! # Slideshow
+//!
+//! Advances through the images on a timer. The slideshow stops after the last image unless it
+//! loops, and in shuffle mode it shows the images in a random order without repeating one until
+//! every image was shown.
+
+use rand::{thread_rng, Rng};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// Seconds each image is shown when no interval is given
+pub const DEFAULT_INTERVAL: f64 = 5.0;
+
+/// Slideshow tracks the timer of the current image and which images were shown
+#[derive(Clone, Debug)]
+pub struct Slideshow {
+    /// how long each image is shown
+    pub interval: Duration,
+    /// starts over once every image was shown
+    pub looping: bool,
+    /// shows the images in a random order
+    pub shuffle: bool,
+    /// when the current image was shown, moved forward by the time spent paused
+    started: Instant,
+    /// time left on the current image while paused
+    paused: Option<Duration>,
+    /// images shown in this round of a shuffled slideshow
+    shown: HashSet<usize>,
+    /// seconds left when the countdown was last taken
+    last_countdown: u64,
+}
+
+impl Slideshow {
+    /// Slideshow showing each image for interval, starting its timer now
+    pub fn new(interval: Duration, looping: bool, shuffle: bool) -> Self {
+        Slideshow {
+            interval,
+            looping,
+            shuffle,
+            started: Instant::now(),
+            paused: None,
+            shown: HashSet::new(),
+            last_countdown: 0,
+        }
+    }
+
+    /// Parses the interval in seconds along with the words "loop" and "shuffle" in any order,
+    /// the interval defaults to DEFAULT_INTERVAL
+    pub fn parse(arguments: &str) -> Result<Self, String> {
+        let mut seconds = DEFAULT_INTERVAL;
+        let (mut looping, mut shuffle) = (false, false);
+        for argument in arguments.split_whitespace() {
+            match argument {
+                "loop" => looping = true,
+                "shuffle" => shuffle = true,
+                _ => {
+                    seconds = match argument.parse::<f64>() {
+                        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => seconds,
+                        _ => {
+                            return Err(format!(
+                                "\"{}\" is not a positive number of seconds",
+                                argument
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+        Ok(Slideshow::new(
+            Duration::from_secs_f64(seconds),
+            looping,
+            shuffle,
+        ))
+    }
+
+    /// Time left before the next image is shown
+    pub fn remaining(&self) -> Duration {
+        match self.paused {
+            Some(remaining) => remaining,
+            None => self.interval.saturating_sub(self.started.elapsed()),
+        }
+    }
+
+    /// Whole seconds left before the next image is shown, rounded up
+    pub fn countdown(&self) -> u64 {
+        let remaining = self.remaining();
+        remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0)
+    }
+
+    /// Whether the countdown changed since the last call, so the infobar shows the seconds left
+    pub fn countdown_changed(&mut self) -> bool {
+        let countdown = self.countdown();
+        let changed = countdown != self.last_countdown;
+        self.last_countdown = countdown;
+        changed
+    }
+
+    /// Whether the timer is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused.is_some()
+    }
+
+    /// Pauses the timer or resumes it with the time that was left
+    pub fn toggle_pause(&mut self) {
+        match self.paused.take() {
+            Some(remaining) => {
+                self.started = Instant::now() - self.interval.saturating_sub(remaining);
+            }
+            None => self.paused = Some(self.remaining()),
+        }
+    }
+
+    /// Starts the timer of the current image over, keeping it paused when it is
+    pub fn restart(&mut self) {
+        self.started = Instant::now();
+        if self.paused.is_some() {
+            self.paused = Some(self.interval);
+        }
+    }
+
+    /// Whether the current image was shown for the whole interval
+    pub fn is_due(&self) -> bool {
+        self.paused.is_none() && self.started.elapsed() >= self.interval
+    }
+
+    /// Index of the image to show after index out of len images and restarts the timer, None
+    /// once the slideshow is over
+    pub fn next(&mut self, index: usize, len: usize) -> Option<usize> {
+        self.restart();
+        if len == 0 {
+            return None;
+        }
+        if !self.shuffle {
+            return if index + 1 < len {
+                Some(index + 1)
+            } else if self.looping {
+                Some(0)
+            } else {
+                None
+            };
+        }
+        self.shown.insert(index);
+        let mut unseen: Vec<usize> = (0..len).filter(|i| !self.shown.contains(i)).collect();
+        if unseen.is_empty() {
+            if !self.looping {
+                return None;
+            }
+            // Start a new round that does not begin with the image just shown
+            self.shown.clear();
+            self.shown.insert(index);
+            unseen = (0..len).filter(|&i| i != index || len == 1).collect();
+        }
+        Some(unseen[thread_rng().gen_range(0, unseen.len())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Slideshow, DEFAULT_INTERVAL};
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_slideshow_arguments() {
+        let slideshow = Slideshow::parse("").unwrap();
+        assert_eq!(
+            slideshow.interval,
+            Duration::from_secs_f64(DEFAULT_INTERVAL)
+        );
+        assert!(!slideshow.looping && !slideshow.shuffle);
+        let slideshow = Slideshow::parse("shuffle 2.5 loop").unwrap();
+        assert_eq!(slideshow.interval, Duration::from_millis(2500));
+        assert!(slideshow.looping && slideshow.shuffle);
+        assert!(Slideshow::parse("0").is_err());
+        assert!(Slideshow::parse("fast").is_err());
+    }
+
+    #[test]
+    fn test_next_stops_or_loops_at_the_end() {
+        let mut slideshow = Slideshow::new(Duration::from_secs(1), false, false);
+        assert_eq!(slideshow.next(0, 3), Some(1));
+        assert_eq!(slideshow.next(2, 3), None);
+        slideshow.looping = true;
+        assert_eq!(slideshow.next(2, 3), Some(0));
+    }
+
+    #[test]
+    fn test_shuffle_shows_every_image_once_per_round() {
+        let mut slideshow = Slideshow::new(Duration::from_secs(1), false, true);
+        let mut index = 0;
+        let mut shown = HashSet::new();
+        shown.insert(index);
+        while let Some(next) = slideshow.next(index, 10) {
+            assert!(shown.insert(next));
+            index = next;
+        }
+        assert_eq!(shown.len(), 10);
+        slideshow.looping = true;
+        let next = slideshow.next(index, 10).unwrap();
+        assert_ne!(next, index);
+    }
+
+    #[test]
+    fn test_pause_keeps_remaining_time() {
+        let mut slideshow = Slideshow::new(Duration::from_secs(60), false, false);
+        slideshow.toggle_pause();
+        assert!(slideshow.is_paused() && !slideshow.is_due());
+        let remaining = slideshow.remaining();
+        assert_eq!(slideshow.remaining(), remaining);
+        assert_eq!(slideshow.countdown(), 60);
+        slideshow.toggle_pause();
+        assert!(!slideshow.is_paused());
+        assert!(slideshow.remaining() <= remaining);
+    }
+}
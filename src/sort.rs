@@ -6,14 +6,20 @@
 // inside of arg!_enum macro call
 #![allow(missing_docs)]
 
+use crate::archive::real_path;
 use core::cmp::Ordering;
 use fs_extra::dir::get_size;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 use std::ffi::OsStr;
+use std::mem;
 use std::path::PathBuf;
+use std::thread;
 use std::time::SystemTime;
 
+/// Fewest paths worth handing to a thread of their own when gathering sort keys
+const MIN_PATHS_PER_THREAD: usize = 256;
+
 /// A structure that takes the SortOrder from command line arguments
 /// and reverse and sorts paths based on original criteria
 // Chosen over functions, due to it being more adaptable in the future
@@ -47,20 +53,37 @@ impl Sorter {
     }
 
     /// Sorts the images based on sort_order, reverses if necessary
+    /// The key of every path is gathered once up front, then paths are sorted stably, so paths
+    /// with equal keys keep their order. Paths whose size or date can't be read go last, reversed
+    /// or not
     pub fn sort<'a>(&self, paths: &'a mut [PathBuf]) -> &'a mut [PathBuf] {
         if self.shuffle {
             let mut rng = thread_rng();
             paths.shuffle(&mut rng);
-        } else {
-            paths.sort_by(|a, b| self.sort_order.file_compare(&a, &b));
-            if self.reverse {
-                paths.reverse();
-            }
+            return paths;
+        }
+        let keys = self.sort_order.keys(paths);
+        let mut keyed: Vec<(Key, PathBuf)> = keys
+            .into_iter()
+            .zip(paths.iter_mut().map(mem::take))
+            .collect();
+        keyed.sort_by(|(a, _), (b, _)| self.sort_order.key_compare(a, b, self.reverse));
+        for (path, (_, sorted)) in paths.iter_mut().zip(keyed) {
+            *path = sorted;
         }
         paths
     }
 }
 
+/// Key is what a path is ordered by, None when the size or date of the file can't be read
+#[derive(Debug, PartialEq)]
+enum Key {
+    Size(Option<u64>),
+    Date(Option<SystemTime>),
+    Name(String),
+    Depth(usize),
+}
+
 // arg_enum! prevents documentation from being written for enum variants
 arg_enum! {
     /// Enum used by clap cli app, in order to parse sorting options
@@ -79,23 +102,77 @@ arg_enum! {
 }
 
 impl SortOrder {
-    /// A comparator for each of the sorting types returns an ordering
-    fn file_compare(&self, a: &PathBuf, b: &PathBuf) -> Ordering {
+    /// Gathers the key of every path, spread over the available cores since reading sizes and
+    /// dates takes a system call per file
+    fn keys(&self, paths: &[PathBuf]) -> Vec<Key> {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = paths.len().div_ceil(threads).max(MIN_PATHS_PER_THREAD);
+        if paths.len() <= chunk_size {
+            return paths.iter().map(|path| self.key(path)).collect();
+        }
+        thread::scope(|scope| {
+            let handles: Vec<_> = paths
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || chunk.iter().map(|path| self.key(path)).collect::<Vec<_>>())
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| match handle.join() {
+                    Ok(keys) => keys,
+                    Err(e) => panic!("Failed to gather sort keys {:?}", e),
+                })
+                .collect()
+        })
+    }
+
+    /// The key path is ordered by
+    fn key(&self, path: &PathBuf) -> Key {
         match self {
-            // if get_size fails just assume that it's size 0
-            SortOrder::Size => get_size(b).unwrap_or(0).cmp(&get_size(a).unwrap_or(0)),
-            SortOrder::Date => file_get_date(b).cmp(&file_get_date(a)),
-            SortOrder::Alphabetical => {
-                let a_no_dot = trim_hidden(a.file_stem());
-                let b_no_dot = trim_hidden(b.file_stem());
-                natord::compare_ignore_case(&a_no_dot, &b_no_dot)
+            // images inside archives take the size and date of their archive
+            SortOrder::Size => Key::Size(get_size(real_path(path)).ok()),
+            SortOrder::Date => Key::Date(file_get_date(path)),
+            SortOrder::Alphabetical => Key::Name(trim_hidden(path.file_stem())),
+            SortOrder::DepthFirst | SortOrder::BreadthFirst => Key::Depth(calculate_depth(path)),
+        }
+    }
+
+    /// A comparator for the keys of each of the sorting types returns an ordering
+    fn key_compare(&self, a: &Key, b: &Key, reverse: bool) -> Ordering {
+        let ordering = match (self, a, b) {
+            (SortOrder::Size, Key::Size(a), Key::Size(b)) => {
+                return largest_first(a.as_ref(), b.as_ref(), reverse)
+            }
+            (SortOrder::Date, Key::Date(a), Key::Date(b)) => {
+                return largest_first(a.as_ref(), b.as_ref(), reverse)
+            }
+            (SortOrder::Alphabetical, Key::Name(a), Key::Name(b)) => {
+                natord::compare_ignore_case(a, b)
             }
-            SortOrder::DepthFirst => calculate_depth(&b).cmp(&calculate_depth(&a)),
-            SortOrder::BreadthFirst => calculate_depth(&a).cmp(&calculate_depth(&b)),
+            (SortOrder::DepthFirst, Key::Depth(a), Key::Depth(b)) => b.cmp(a),
+            (SortOrder::BreadthFirst, Key::Depth(a), Key::Depth(b)) => a.cmp(b),
+            _ => Ordering::Equal,
+        };
+        if reverse {
+            ordering.reverse()
+        } else {
+            ordering
         }
     }
 }
 
+/// Orders the largest value first, or the smallest when reversed, with missing values last
+fn largest_first<T: Ord>(a: Option<&T>, b: Option<&T>, reverse: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) if reverse => a.cmp(b),
+        (Some(a), Some(b)) => b.cmp(a),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
 /// Get the depth of a path + 1 due to the filename being included
 fn calculate_depth(path: &PathBuf) -> usize {
     path.ancestors().count()
@@ -112,20 +189,63 @@ fn trim_hidden(file_name: Option<&OsStr>) -> String {
     }
 }
 
-/// Get the time a file was last modified, if this function fails it returns Now
-fn file_get_date(path: &PathBuf) -> SystemTime {
-    let metadata = match path.metadata() {
-        Ok(data) => data,
-        Err(e) => {
-            eprintln!("{}", e);
-            return SystemTime::now();
-        }
-    };
-    match metadata.modified() {
-        Ok(time) => time,
-        Err(e) => {
-            eprintln!("{}", e);
-            SystemTime::now()
-        }
+/// Get the time a file was last modified, None if it can't be read
+fn file_get_date(path: &PathBuf) -> Option<SystemTime> {
+    real_path(path)
+        .metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SortOrder, Sorter};
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn sorted(order: SortOrder, reverse: bool, paths: &[PathBuf]) -> Vec<PathBuf> {
+        let mut paths = paths.to_vec();
+        Sorter::new(order, reverse, false).sort(&mut paths);
+        paths
+    }
+
+    #[test]
+    fn test_unreadable_sizes_go_last() {
+        let dir = std::env::temp_dir().join("riv_test_sort_size");
+        fs::create_dir_all(&dir).unwrap();
+        let small = dir.join("small.png");
+        let large = dir.join("large.png");
+        let missing = dir.join("missing.png");
+        fs::write(&small, b"a").unwrap();
+        fs::write(&large, b"abc").unwrap();
+        let paths = vec![missing.clone(), small.clone(), large.clone()];
+        assert_eq!(
+            sorted(SortOrder::Size, false, &paths),
+            vec![large.clone(), small.clone(), missing.clone()]
+        );
+        assert_eq!(
+            sorted(SortOrder::Size, true, &paths),
+            vec![small, large, missing]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_equal_keys_keep_their_order() {
+        let paths: Vec<PathBuf> = ["b/x.png", "a/y.png", "c.png", "a/z.png"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        let expected: Vec<PathBuf> = ["b/x.png", "a/y.png", "a/z.png", "c.png"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(sorted(SortOrder::DepthFirst, false, &paths), expected);
+        let many: Vec<PathBuf> = (0..2000)
+            .map(|i| PathBuf::from(format!("{}.png", i)))
+            .collect();
+        let mut shuffled = many.clone();
+        shuffled.reverse();
+        assert_eq!(sorted(SortOrder::Alphabetical, false, &shuffled), many);
     }
 }
@@ -0,0 +1,76 @@
+//! # Stdin
+//!
+//! An image piped to riv with `riv -` is read from standard input once at start up and kept in
+//! memory. It is addressed by the virtual path PATH, which is not a file on disk, so file
+//! operations refuse it and `:export` saves it instead.
+
+use crate::formats::Format;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{PoisonError, RwLock};
+
+/// Virtual path of the image read from standard input
+pub const PATH: &str = "<stdin>";
+
+lazy_static! {
+    static ref BYTES: RwLock<Vec<u8>> = RwLock::new(Vec::new());
+}
+
+/// Reads the image piped to riv and returns its virtual path
+pub fn read() -> Result<PathBuf, String> {
+    read_from(std::io::stdin())
+}
+
+/// Reads the image from reader in place of standard input and returns its virtual path
+pub fn read_from<R: Read>(mut reader: R) -> Result<PathBuf, String> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Error reading standard input: {}", e))?;
+    if bytes.is_empty() {
+        return Err("Nothing was piped to standard input".to_string());
+    }
+    *BYTES.write().unwrap_or_else(PoisonError::into_inner) = bytes;
+    Ok(PathBuf::from(PATH))
+}
+
+/// Whether the path is the virtual path of the image read from standard input
+pub fn is_stdin(path: &Path) -> bool {
+    path == Path::new(PATH)
+}
+
+/// The bytes of the image read from standard input
+pub fn bytes() -> Vec<u8> {
+    BYTES.read().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+/// Name to save the image read from standard input as, with the extension of its format when it
+/// can be detected
+pub fn file_name() -> String {
+    match Format::sniff(&BYTES.read().unwrap_or_else(PoisonError::into_inner)) {
+        Some(format) => format!("stdin.{}", format),
+        None => "stdin".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bytes, file_name, is_stdin, read_from, PATH};
+    use std::path::Path;
+
+    #[test]
+    fn test_read_from_keeps_bytes() {
+        assert!(read_from(&b""[..]).is_err());
+        let path = read_from(&b"\x89PNG\r\n\x1a\n"[..]).unwrap();
+        assert!(is_stdin(&path));
+        assert_eq!(bytes(), b"\x89PNG\r\n\x1a\n".to_vec());
+        assert_eq!(file_name(), "stdin.png");
+    }
+
+    #[test]
+    fn test_is_stdin_only_matches_virtual_path() {
+        assert!(is_stdin(Path::new(PATH)));
+        assert!(!is_stdin(Path::new("-")));
+        assert!(!is_stdin(Path::new("stdin.png")));
+    }
+}
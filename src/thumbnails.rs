@@ -0,0 +1,144 @@
+//! # Thumbnails
+//!
+//! Decodes small thumbnails of images on background threads, so browsing a grid of them never
+//! waits on a decode. Only the thumbnails last requested are decoded, the ones that scrolled out
+//! of view are dropped from the queue before a worker gets to them.
+
+use crate::decode::{self, Frame};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+/// Most threads decoding thumbnails in the background
+const MAX_WORKERS: usize = 4;
+
+/// Queue is the work shared between the thumbnailer and its workers
+#[derive(Default)]
+struct Queue {
+    /// paths waiting for a worker, the most wanted first
+    pending: VecDeque<PathBuf>,
+    /// paths a worker is decoding
+    decoding: Vec<PathBuf>,
+    /// decoded thumbnails waiting to be taken
+    finished: Vec<(PathBuf, Result<Frame, String>)>,
+    /// workers exit once the thumbnailer is dropped
+    stopped: bool,
+}
+
+/// Shared holds the queue along with the condition workers wait on
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Thumbnailer decodes thumbnails with a pool of worker threads
+pub struct Thumbnailer {
+    shared: Arc<Shared>,
+}
+
+impl Thumbnailer {
+    /// Starts the workers decoding thumbnails at most size pixels a side
+    pub fn new(size: u32) -> Self {
+        let shared = Arc::new(Shared::default());
+        let parallelism = thread::available_parallelism().map_or(1, |n| n.get());
+        for _ in 0..parallelism.min(MAX_WORKERS) {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || work(&shared, size));
+        }
+        Thumbnailer { shared }
+    }
+
+    /// Queues the thumbnails of paths in order, replacing what was queued before. Thumbnails
+    /// being decoded or waiting to be taken are not queued again
+    pub fn request(&self, paths: &[PathBuf]) {
+        let mut queue = self.shared.lock();
+        let pending: VecDeque<PathBuf> = paths
+            .iter()
+            .filter(|path| {
+                !queue.decoding.contains(path) && !queue.finished.iter().any(|(p, _)| p == *path)
+            })
+            .cloned()
+            .collect();
+        queue.pending = pending;
+        self.shared.changed.notify_all();
+    }
+
+    /// Takes the thumbnails decoded since the last call, without waiting
+    pub fn finished(&self) -> Vec<(PathBuf, Result<Frame, String>)> {
+        self.shared.lock().finished.drain(..).collect()
+    }
+}
+
+impl Drop for Thumbnailer {
+    fn drop(&mut self) {
+        self.shared.lock().stopped = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+/// Decodes queued thumbnails until the thumbnailer is dropped
+fn work(shared: &Shared, size: u32) {
+    let mut queue = shared.lock();
+    loop {
+        if queue.stopped {
+            return;
+        }
+        let path = match queue.pending.pop_front() {
+            Some(path) => path,
+            None => {
+                queue = shared
+                    .changed
+                    .wait(queue)
+                    .unwrap_or_else(PoisonError::into_inner);
+                continue;
+            }
+        };
+        queue.decoding.push(path.clone());
+        drop(queue);
+
+        let thumbnail = decode::thumbnail(&path, size);
+
+        queue = shared.lock();
+        queue.decoding.retain(|decoding| decoding != &path);
+        queue.finished.push((path, thumbnail));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Thumbnailer;
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_thumbnails_are_decoded_once_requested() {
+        let paths = vec![
+            PathBuf::from("tests/fixtures/rgba.qoi"),
+            PathBuf::from("tests/fixtures/missing.png"),
+        ];
+        let thumbnailer = Thumbnailer::new(1);
+        thumbnailer.request(&paths);
+        let mut finished = Vec::new();
+        for _ in 0..1000 {
+            finished.extend(thumbnailer.finished());
+            if finished.len() == paths.len() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        finished.sort_by(|(a, _), (b, _)| a.cmp(b));
+        assert!(finished[0].1.is_err());
+        let thumbnail = finished[1].1.as_ref().unwrap();
+        assert_eq!((thumbnail.width, thumbnail.height), (1, 1));
+        assert!(thumbnailer.finished().is_empty());
+    }
+}
@@ -0,0 +1,206 @@
+//! # Tiles
+//!
+//! Renderers limit how large a texture may be, often to 8192 or 16384 pixels a side. Images larger
+//! than that are split into a grid of textures that are drawn side by side, rotated and flipped
+//! as one image. Tiles outside of the window are not drawn.
+
+use crate::decode::Frame;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{Texture, TextureQuery, WindowCanvas};
+
+/// Tile is one texture of an image split into tiles
+pub struct Tile<'a> {
+    /// texture holding the pixels of the tile
+    pub texture: Texture<'a>,
+    /// area of the image the tile covers, in image pixels
+    pub area: Rect,
+}
+
+/// Tiles holds the textures of an image, a single one when it fits in a texture
+pub struct Tiles<'a> {
+    tiles: Vec<Tile<'a>>,
+    width: u32,
+    height: u32,
+}
+
+impl<'a> Tiles<'a> {
+    /// Tiles of an image of width by height pixels, the tiles must cover the whole image
+    pub fn new(tiles: Vec<Tile<'a>>, width: u32, height: u32) -> Self {
+        Tiles {
+            tiles,
+            width,
+            height,
+        }
+    }
+
+    /// Format, access and size of the whole image, as if it was a single texture
+    pub fn query(&self) -> TextureQuery {
+        let query = self.tiles[0].texture.query();
+        TextureQuery {
+            width: self.width,
+            height: self.height,
+            ..query
+        }
+    }
+
+    /// Uploads the pixels of a frame the size of the image into the tiles
+    pub fn update(&mut self, frame: &Frame) -> Result<(), String> {
+        let pitch = frame.pitch();
+        for tile in &mut self.tiles {
+            let start = tile.area.y() as usize * pitch + tile.area.x() as usize * 4;
+            tile.texture
+                .update(None, &frame.pixels[start..], pitch)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Draws the image into dst rotated clockwise by angle degrees around the center of dst, with
+    /// the flips applied to the whole image rather than to each tile
+    pub fn draw(
+        &self,
+        canvas: &mut WindowCanvas,
+        dst: Rect,
+        angle: f64,
+        flip_horizontal: bool,
+        flip_vertical: bool,
+    ) -> Result<(), String> {
+        let viewport = canvas.viewport();
+        let window = Rect::new(0, 0, viewport.width(), viewport.height());
+        for tile in &self.tiles {
+            let (tile_dst, center) = place(
+                tile.area,
+                self.width,
+                self.height,
+                dst,
+                flip_horizontal,
+                flip_vertical,
+            );
+            if !bounds(tile_dst, dst.center(), angle).has_intersection(window) {
+                continue;
+            }
+            canvas.copy_ex(
+                &tile.texture,
+                None,
+                tile_dst,
+                angle,
+                center,
+                flip_horizontal,
+                flip_vertical,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits an image of width by height pixels into tiles at most max pixels a side, row by row
+pub fn grid(width: u32, height: u32, max: u32) -> Vec<Rect> {
+    let max = max.max(1);
+    let mut areas = Vec::new();
+    for y in (0..height).step_by(max as usize) {
+        for x in (0..width).step_by(max as usize) {
+            let area_width = max.min(width - x);
+            let area_height = max.min(height - y);
+            areas.push(Rect::new(x as i32, y as i32, area_width, area_height));
+        }
+    }
+    areas
+}
+
+/// Places the tile covering area of a width by height image drawn into dst, mirroring its position
+/// for the flips. Returns the rectangle to draw the tile into along with the center of dst
+/// relative to it, which the tile is rotated around
+fn place(
+    area: Rect,
+    width: u32,
+    height: u32,
+    dst: Rect,
+    flip_horizontal: bool,
+    flip_vertical: bool,
+) -> (Rect, Point) {
+    let (mut left, mut right) = (area.left(), area.right());
+    if flip_horizontal {
+        let (l, r) = (width as i32 - right, width as i32 - left);
+        left = l;
+        right = r;
+    }
+    let (mut top, mut bottom) = (area.top(), area.bottom());
+    if flip_vertical {
+        let (t, b) = (height as i32 - bottom, height as i32 - top);
+        top = t;
+        bottom = b;
+    }
+    // Edges are scaled rather than sizes, so neighbouring tiles meet without gaps
+    let scale_x = dst.width() as f64 / width as f64;
+    let scale_y = dst.height() as f64 / height as f64;
+    let x = |edge: i32| dst.x() + (edge as f64 * scale_x).round() as i32;
+    let y = |edge: i32| dst.y() + (edge as f64 * scale_y).round() as i32;
+    let tile_dst = Rect::new(
+        x(left),
+        y(top),
+        (x(right) - x(left)).max(1) as u32,
+        (y(bottom) - y(top)).max(1) as u32,
+    );
+    let center = dst.center() - tile_dst.top_left();
+    (tile_dst, center)
+}
+
+/// Bounding box of rect once rotated clockwise around center by a multiple of 90 degrees
+fn bounds(rect: Rect, center: Point, angle: f64) -> Rect {
+    let (dx, dy) = (
+        rect.center().x() - center.x(),
+        rect.center().y() - center.y(),
+    );
+    let quarter_turns = (angle / 90.0).round() as i32;
+    let (dx, dy, width, height) = match quarter_turns.rem_euclid(4) {
+        1 => (-dy, dx, rect.height(), rect.width()),
+        2 => (-dx, -dy, rect.width(), rect.height()),
+        3 => (dy, -dx, rect.height(), rect.width()),
+        _ => (dx, dy, rect.width(), rect.height()),
+    };
+    Rect::from_center(center.offset(dx, dy), width, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bounds, grid, place};
+    use sdl2::rect::{Point, Rect};
+
+    #[test]
+    fn test_grid_covers_image() {
+        assert_eq!(grid(10, 4, 16), vec![Rect::new(0, 0, 10, 4)]);
+        assert_eq!(
+            grid(10, 4, 4),
+            vec![
+                Rect::new(0, 0, 4, 4),
+                Rect::new(4, 0, 4, 4),
+                Rect::new(8, 0, 2, 4),
+            ]
+        );
+        assert_eq!(grid(3, 5, 3).len(), 2);
+    }
+
+    #[test]
+    fn test_place_mirrors_flipped_tiles() {
+        let dst = Rect::new(100, 50, 200, 100);
+        let area = Rect::new(0, 0, 10, 10);
+        let (tile, center) = place(area, 20, 10, dst, false, false);
+        assert_eq!(tile, Rect::new(100, 50, 100, 100));
+        assert_eq!(center, Point::new(100, 50));
+        let (tile, center) = place(area, 20, 10, dst, true, false);
+        assert_eq!(tile, Rect::new(200, 50, 100, 100));
+        assert_eq!(center, Point::new(0, 50));
+        let (tile, _) = place(Rect::new(0, 0, 20, 4), 20, 10, dst, false, true);
+        assert_eq!(tile, Rect::new(100, 110, 200, 40));
+    }
+
+    #[test]
+    fn test_bounds_rotates_around_center() {
+        let rect = Rect::new(0, 0, 100, 50);
+        let center = Point::new(100, 50);
+        assert_eq!(bounds(rect, center, 0.0), rect);
+        assert_eq!(bounds(rect, center, 90.0), Rect::new(100, -50, 50, 100));
+        assert_eq!(bounds(rect, center, 180.0), Rect::new(100, 50, 100, 50));
+        assert_eq!(bounds(rect, center, 270.0), Rect::new(50, 50, 50, 100));
+    }
+}
@@ -2,10 +2,13 @@
 //!
 //! The UI module contains logic for matching keyboard and system events
 
+use crate::decode::DEFAULT_GAMMA;
+use crate::flipbook::Flipbook;
+use crate::slideshow::Slideshow;
 use sdl2::event::Event;
 use sdl2::keyboard::Mod;
 use sdl2::mouse::MouseButton;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 /// Action represents the possible actions that could result from an event
 #[derive(Debug, Clone)]
@@ -27,9 +30,16 @@ pub enum Action<'a> {
     SwitchNormalMode,
     /// Switches to MultiNormalMode for bulk actions
     SwitchMultiNormalMode,
+    /// Switches to the gallery of thumbnails
+    SwitchGalleryMode,
+    /// Moves the selection of the gallery in the direction of the SelectAction variant
+    Select(SelectAction),
     /// The app should switch its current image viewing preference of fitting the
     /// image to screen or displaying the actual size as actual size
     ToggleFit,
+    /// Left click at x, y while the filmstrip is shown, jumps to the image clicked in the strip
+    /// and toggles fit anywhere else
+    Click(i32, i32),
     /// Centres the image
     CenterImage,
     /// Flip the image horizontally
@@ -54,12 +64,28 @@ pub enum Action<'a> {
     Rotate(RotationDirection),
     /// Pan pans the picture in the direction of the PanAction variant
     Pan(PanAction),
+    /// Pauses or resumes playback of animated images
+    TogglePlayback,
+    /// Steps through the frames of an animated image in the direction of the FrameAction variant
+    Frame(FrameAction),
+    /// Changes the playback speed of animated images depending on the SpeedAction variant
+    Speed(SpeedAction),
+    /// Moves between the pages of the current image in the direction of the PageAction variant
+    Page(PageAction),
+    /// Changes the exposure of high dynamic range images depending on the ToneAction variant
+    Exposure(ToneAction),
+    /// Changes the gamma of high dynamic range images depending on the ToneAction variant
+    Gamma(ToneAction),
     /// Copy indicates the app should copy the image in response to this event
     Copy,
     /// Move indicates the app should move the image in response to this event
     Move,
     /// Delete indicates the app should delete the image in response to this event
     Delete,
+    /// Changes which images are marked for file operations depending on the MarkAction variant
+    Mark(MarkAction),
+    /// Stores or jumps to a named bookmark depending on the BookmarkAction variant
+    Bookmark(BookmarkAction),
     /// Trash indicates the app should move the image to a trash folder
     Trash,
     /// Noop indicates the app should not respond to this event
@@ -177,6 +203,84 @@ pub enum PanAction {
     Down,
 }
 
+/// FrameAction contains the variants of a possible frame step. Next | Prev
+#[derive(Debug, Clone)]
+pub enum FrameAction {
+    /// Next steps to the next frame
+    Next,
+    /// Prev steps to the previous frame
+    Prev,
+}
+
+/// SpeedAction contains the variants of a possible playback speed change. Faster | Slower
+#[derive(Debug, Clone)]
+pub enum SpeedAction {
+    /// Faster doubles the playback speed
+    Faster,
+    /// Slower halves the playback speed
+    Slower,
+}
+
+/// PageAction contains the variants of a possible page change. Next | Prev
+#[derive(Debug, Clone)]
+pub enum PageAction {
+    /// Next moves to the next page
+    Next,
+    /// Prev moves to the previous page
+    Prev,
+}
+
+/// ToneAction contains the variants of a possible exposure or gamma change. Increase | Decrease
+#[derive(Debug, Clone)]
+pub enum ToneAction {
+    /// Increase raises the exposure or gamma by one step
+    Increase,
+    /// Decrease lowers the exposure or gamma by one step
+    Decrease,
+}
+
+/// SelectAction contains the variants of a possible move of the gallery selection
+#[derive(Debug, Clone)]
+pub enum SelectAction {
+    /// Left selects the previous image
+    Left,
+    /// Right selects the next image
+    Right,
+    /// Up selects the image a row up
+    Up,
+    /// Down selects the image a row down
+    Down,
+    /// PageUp selects the image a screen of rows up
+    PageUp,
+    /// PageDown selects the image a screen of rows down
+    PageDown,
+    /// Number selects the image numbered by the count typed before it
+    Number,
+}
+
+/// MarkAction contains the variants of a possible change of the marked images
+#[derive(Debug, Clone)]
+pub enum MarkAction {
+    /// Toggle marks or unmarks the current image and the ones after it
+    Toggle,
+    /// All marks every image
+    All,
+    /// Invert marks the unmarked images and unmarks the marked ones
+    Invert,
+    /// Clear unmarks every image
+    Clear,
+}
+
+/// BookmarkAction contains the variants of a possible use of a bookmark, each with the letter
+/// naming the bookmark
+#[derive(Debug, Clone)]
+pub enum BookmarkAction {
+    /// Set stores the current image under the letter
+    Set(char),
+    /// Jump moves to the image stored under the letter
+    Jump(char),
+}
+
 /// Modal setting for Program, this dictates the commands that are available to the user
 #[derive(Debug, PartialEq, Clone)]
 pub enum Mode {
@@ -185,6 +289,9 @@ pub enum Mode {
     /// Normal mode is switched to receiving the amount of times to perform
     /// the same action
     MultiNormal,
+    /// Mode that shows the images as a grid of thumbnails, moves the selection through them and
+    /// acts on the selected image
+    Gallery,
     /// Mode that is built off of user input, allows switching the current glob
     /// string is the input to display on the infobar
     Command(String),
@@ -206,6 +313,10 @@ pub enum HelpRender {
     Normal,
     /// Should render command mode help
     Command,
+    /// Should render the list of bookmarks
+    Bookmarks,
+    /// Should render the statistics of the texture caches
+    Debug,
 }
 
 /// Storage for state across functions
@@ -226,6 +337,8 @@ impl<'a> Default for Register<'a> {
 pub struct State<'a> {
     /// render_infobar determines whether or not the info bar should be rendered.
     pub render_infobar: bool,
+    /// render_filmstrip determines whether the strip of neighbouring thumbnails is rendered.
+    pub render_filmstrip: bool,
     /// render_help determines whether or not the help info should be rendered.
     pub render_help: HelpRender,
     /// Tracks fullscreen state of app.
@@ -252,9 +365,237 @@ pub struct State<'a> {
     pub rerender_time: Option<Instant>,
     /// Store
     pub register: Register<'a>,
+    /// Playback state of the current image when it is animated
+    pub playback: Playback,
+    /// Images are rotated and flipped upright from their EXIF orientation when loaded
+    pub auto_rotate: bool,
+    /// Whether the current image has an embedded color profile and if it was converted
+    pub color_profile: ColorProfile,
+    /// Exposure and gamma high dynamic range images are mapped to the display with
+    pub tone: Tone,
+    /// Number of images found so far while the glob is still running in the background
+    pub scanning: Option<usize>,
+    /// Layout and input state of the gallery of thumbnails
+    pub gallery: Gallery,
+    /// Timer advancing through the images while a slideshow is running
+    pub slideshow: Option<Slideshow>,
+    /// Timing and range of flipbook playback while the images are played as a movie
+    pub flipbook: Option<Flipbook>,
+    /// Key typed to set (M) or jump to (') a bookmark, waiting for the letter naming it
+    pub bookmark_key: Option<char>,
+}
+
+/// Gallery tracks the grid of thumbnails shown instead of the current image
+#[derive(Default)]
+pub struct Gallery {
+    /// The grid of thumbnails is shown, also while a message is displayed over it
+    pub open: bool,
+    /// First row of thumbnails in view
+    pub top_row: usize,
+    /// Count typed before an action, 0 when none was typed
+    pub count: usize,
+}
+
+/// ColorProfile is the color management state of the current image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorProfile {
+    /// The image has no embedded ICC profile
+    Untagged,
+    /// The image has an embedded ICC profile that was not applied
+    Tagged,
+    /// The image was converted from its embedded ICC profile to the display profile
+    Converted,
+}
+
+/// Playback tracks which frame of an animated image is displayed and how fast frames advance
+pub struct Playback {
+    /// Index of the frame being displayed
+    pub frame: usize,
+    /// Number of frames in the current image, 1 for still images
+    pub frame_count: usize,
+    /// Animation is stopped on the current frame
+    pub paused: bool,
+    /// Multiplier applied to the delay of every frame, 2.0 plays twice as fast
+    pub speed: f32,
+    /// The time the current frame was first displayed
+    pub frame_start: Instant,
+    /// Number of times the animation plays before pausing on its last frame, None repeats forever
+    pub plays: Option<u32>,
+    /// Number of times the animation has played through since it started or was last finished
+    pub played: u32,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            frame: 0,
+            frame_count: 1,
+            paused: false,
+            speed: 1.0,
+            frame_start: Instant::now(),
+            plays: None,
+            played: 0,
+        }
+    }
+}
+
+impl Playback {
+    /// Resets playback to the first frame of a newly loaded image, keeping the speed
+    pub fn reset(&mut self, frame_count: usize, plays: Option<u32>) {
+        self.frame = 0;
+        self.frame_count = frame_count;
+        self.paused = false;
+        self.frame_start = Instant::now();
+        self.plays = plays;
+        self.played = 0;
+    }
+
+    /// Moves past every frame whose delay has elapsed by `now`, `delay` gives the delay of a frame
+    /// at original speed. When the last play finishes playback pauses on the last frame.
+    /// Returns whether the frame or paused state changed
+    pub fn advance<F>(&mut self, now: Instant, delay: F) -> bool
+    where
+        F: Fn(usize) -> Duration,
+    {
+        if self.paused || !self.is_animated() {
+            return false;
+        }
+        let mut elapsed = now.saturating_duration_since(self.frame_start);
+        let mut changed = false;
+        loop {
+            let frame_delay = delay(self.frame);
+            let frame_delay = Duration::from_nanos(
+                (frame_delay.as_nanos() as f64 / f64::from(self.speed)) as u64,
+            );
+            if elapsed < frame_delay {
+                break;
+            }
+            elapsed -= frame_delay;
+            if self.frame + 1 < self.frame_count {
+                self.frame += 1;
+                changed = true;
+                continue;
+            }
+            self.played = self.played.saturating_add(1);
+            if let Some(plays) = self.plays {
+                if self.played >= plays {
+                    // Stay on the last frame, resuming plays the animation again
+                    self.paused = true;
+                    self.played = 0;
+                    elapsed = Duration::from_secs(0);
+                    changed = true;
+                    break;
+                }
+            }
+            self.frame = 0;
+            changed = true;
+        }
+        // Keep time spent past the frame boundary so playback does not drift
+        self.frame_start = now - elapsed;
+        changed
+    }
+
+    /// Whether there is more than one frame to play
+    pub fn is_animated(&self) -> bool {
+        self.frame_count > 1
+    }
+
+    /// Moves forward `times` frames, wrapping around to the first frame
+    pub fn next_frame(&mut self, times: usize) {
+        if self.frame_count == 0 {
+            return;
+        }
+        self.frame = (self.frame + times % self.frame_count) % self.frame_count;
+        self.frame_start = Instant::now();
+    }
+
+    /// Moves back `times` frames, wrapping around to the last frame
+    pub fn prev_frame(&mut self, times: usize) {
+        if self.frame_count == 0 {
+            return;
+        }
+        self.frame = (self.frame + self.frame_count - times % self.frame_count) % self.frame_count;
+        self.frame_start = Instant::now();
+    }
+
+    /// Doubles playback speed `times` times, capped at MAX_SPEED
+    pub fn speed_up(&mut self, times: usize) {
+        let speed_times = cap_times(times);
+        self.speed = (self.speed * 2f32.powi(speed_times)).min(MAX_SPEED);
+    }
+
+    /// Halves playback speed `times` times, capped at MIN_SPEED
+    pub fn slow_down(&mut self, times: usize) {
+        let speed_times = cap_times(times);
+        self.speed = (self.speed / 2f32.powi(speed_times)).max(MIN_SPEED);
+    }
+}
+
+/// Fastest an animation can be played relative to its original speed
+const MAX_SPEED: f32 = 16.0;
+/// Slowest an animation can be played relative to its original speed
+const MIN_SPEED: f32 = 1.0 / 16.0;
+
+/// Tone holds how the float pixels of high dynamic range images are mapped to 8 bits. It is kept
+/// across images so a series of exposures can be compared
+pub struct Tone {
+    /// Stops the light is scaled by, each stop doubles or halves it
+    pub exposure: f32,
+    /// Gamma the light is encoded with
+    pub gamma: f32,
+    /// Whether the current image is high dynamic range and is affected by tone changes
+    pub hdr: bool,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            gamma: DEFAULT_GAMMA,
+            hdr: false,
+        }
+    }
 }
 
+impl Tone {
+    /// Raises exposure by `times` steps, capped at MAX_EXPOSURE
+    pub fn increase_exposure(&mut self, times: usize) {
+        let steps = cap_times(times) as f32;
+        self.exposure = (self.exposure + steps * EXPOSURE_STEP).min(MAX_EXPOSURE);
+    }
+
+    /// Lowers exposure by `times` steps, capped at -MAX_EXPOSURE
+    pub fn decrease_exposure(&mut self, times: usize) {
+        let steps = cap_times(times) as f32;
+        self.exposure = (self.exposure - steps * EXPOSURE_STEP).max(-MAX_EXPOSURE);
+    }
+
+    /// Raises gamma by `times` steps, capped at MAX_GAMMA
+    pub fn increase_gamma(&mut self, times: usize) {
+        let steps = cap_times(times) as f32;
+        self.gamma = (self.gamma + steps * GAMMA_STEP).min(MAX_GAMMA);
+    }
+
+    /// Lowers gamma by `times` steps, capped at MIN_GAMMA
+    pub fn decrease_gamma(&mut self, times: usize) {
+        let steps = cap_times(times) as f32;
+        self.gamma = (self.gamma - steps * GAMMA_STEP).max(MIN_GAMMA);
+    }
+}
+
+/// Stops of exposure added or removed by one exposure change
+const EXPOSURE_STEP: f32 = 0.5;
+/// Furthest exposure can be moved in either direction
+const MAX_EXPOSURE: f32 = 16.0;
+/// Gamma added or removed by one gamma change
+const GAMMA_STEP: f32 = 0.1;
+/// Highest gamma light can be encoded with
+const MAX_GAMMA: f32 = 5.0;
+/// Lowest gamma light can be encoded with
+const MIN_GAMMA: f32 = 0.2;
+
 /// Rotation angle for image
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum RotAngle {
     /// 0 degree rotation
     Up,
@@ -291,6 +632,7 @@ impl<'a> Default for State<'a> {
     fn default() -> Self {
         Self {
             render_infobar: true,
+            render_filmstrip: false,
             render_help: HelpRender::None,
             fullscreen: false,
             mode: Mode::Normal,
@@ -305,6 +647,15 @@ impl<'a> Default for State<'a> {
             register: Register {
                 ..Default::default()
             },
+            playback: Playback::default(),
+            auto_rotate: true,
+            color_profile: ColorProfile::Untagged,
+            tone: Tone::default(),
+            scanning: None,
+            gallery: Gallery::default(),
+            slideshow: None,
+            flipbook: None,
+            bookmark_key: None,
         }
     }
 }
@@ -313,7 +664,7 @@ impl<'a> State<'a> {
     /// Increases zoom scale. Does not render image
     pub fn zoom_in(&mut self, times: usize) {
         let zoom_factor: f32 = 1.1;
-        let zoom_times = cap_zoom_times(times);
+        let zoom_times = cap_times(times);
 
         self.scale *= zoom_factor.powi(zoom_times);
     }
@@ -321,10 +672,29 @@ impl<'a> State<'a> {
     /// Decreases zoom scale. Does not render image
     pub fn zoom_out(&mut self, times: usize) {
         let zoom_factor: f32 = 1.1;
-        let zoom_times = cap_zoom_times(times);
+        let zoom_times = cap_times(times);
 
         self.scale /= zoom_factor.powi(zoom_times);
     }
+
+    /// Sets the rotation and flips that display an image with the EXIF orientation upright
+    /// Orientations other than 2 to 8 are already upright
+    pub fn orient(&mut self, orientation: u32) {
+        // Flips are applied before the rotation when rendering
+        let (rot_angle, flip_horizontal, flip_vertical) = match orientation {
+            2 => (RotAngle::Up, true, false),
+            3 => (RotAngle::Down, false, false),
+            4 => (RotAngle::Up, false, true),
+            5 => (RotAngle::Left, true, false),
+            6 => (RotAngle::Right, false, false),
+            7 => (RotAngle::Right, true, false),
+            8 => (RotAngle::Left, false, false),
+            _ => (RotAngle::Up, false, false),
+        };
+        self.rot_angle = rot_angle;
+        self.flip_horizontal = flip_horizontal;
+        self.flip_vertical = flip_vertical;
+    }
 }
 
 impl<'a> State<'a> {
@@ -397,11 +767,27 @@ pub fn process_multi_normal_mode<'a>(
                 state.render_infobar = !state.render_infobar;
                 (Action::ReRender, times).into()
             }
+            "F" => {
+                state.render_filmstrip = !state.render_filmstrip;
+                (Action::ReRender, times).into()
+            }
             "v" => (Action::FlipVertical, times).into(),
+            "x" => (Action::Mark(MarkAction::Toggle), times).into(),
             "w" => (Action::SkipForward, times).into(),
             "b" => (Action::SkipBack, times).into(),
             "z" => (Action::ToggleFit, times).into(),
             "Z" => (Action::CenterImage, times).into(),
+            " " => (Action::TogglePlayback, times).into(),
+            "]" => (Action::Frame(FrameAction::Next), times).into(),
+            "[" => (Action::Frame(FrameAction::Prev), times).into(),
+            "}" => (Action::Speed(SpeedAction::Faster), times).into(),
+            "{" => (Action::Speed(SpeedAction::Slower), times).into(),
+            "n" => (Action::Page(PageAction::Next), times).into(),
+            "p" => (Action::Page(PageAction::Prev), times).into(),
+            "e" => (Action::Exposure(ToneAction::Increase), times).into(),
+            "E" => (Action::Exposure(ToneAction::Decrease), times).into(),
+            "y" => (Action::Gamma(ToneAction::Increase), times).into(),
+            "Y" => (Action::Gamma(ToneAction::Decrease), times).into(),
             _ => MultiNormalAction::Noop,
         },
 
@@ -455,6 +841,26 @@ pub fn process_normal_mode<'a>(state: &mut State<'a>, event: &Event) -> ProcessA
     match event {
         Event::Quit { .. } => Action::Quit.into(),
 
+        // The letter after ' or M names the bookmark, anything else cancels it
+        Event::TextInput { text, .. } if state.bookmark_key.is_some() => {
+            let letter = text
+                .chars()
+                .next()
+                .filter(|c| c.is_ascii_lowercase() && text.len() == 1);
+            match (state.bookmark_key.take(), letter) {
+                (Some('M'), Some(letter)) => Action::Bookmark(BookmarkAction::Set(letter)).into(),
+                (Some(_), Some(letter)) => Action::Bookmark(BookmarkAction::Jump(letter)).into(),
+                _ => Action::Noop.into(),
+            }
+        }
+        Event::KeyDown {
+            keycode: Some(Escape),
+            ..
+        } if state.bookmark_key.is_some() => {
+            state.bookmark_key.take();
+            Action::Noop.into()
+        }
+
         Event::TextInput { text, .. } => match text.as_str() {
             // Number of times to repeat operation
             // 0 is not captured for first digit as it does not impact counts
@@ -465,6 +871,10 @@ pub fn process_normal_mode<'a>(state: &mut State<'a>, event: &Event) -> ProcessA
                 state.register.cur_action.times = first_digit;
                 Action::SwitchMultiNormalMode.into()
             }
+            "'" | "M" => {
+                state.bookmark_key = text.chars().next();
+                Action::Noop.into()
+            }
             "c" => Action::Copy.into(),
             "d" => Action::Trash.into(),
             "D" => Action::Delete.into(),
@@ -495,11 +905,30 @@ pub fn process_normal_mode<'a>(state: &mut State<'a>, event: &Event) -> ProcessA
                 state.render_infobar = !state.render_infobar;
                 Action::ReRender.into()
             }
+            "F" => {
+                state.render_filmstrip = !state.render_filmstrip;
+                Action::ReRender.into()
+            }
             "v" => Action::FlipVertical.into(),
+            "x" => Action::Mark(MarkAction::Toggle).into(),
+            "X" => Action::Mark(MarkAction::Invert).into(),
+            "a" => Action::Mark(MarkAction::All).into(),
+            "u" => Action::Mark(MarkAction::Clear).into(),
             "w" => Action::SkipForward.into(),
             "b" => Action::SkipBack.into(),
             "z" => Action::ToggleFit.into(),
             "Z" => Action::CenterImage.into(),
+            " " => Action::TogglePlayback.into(),
+            "]" => Action::Frame(FrameAction::Next).into(),
+            "[" => Action::Frame(FrameAction::Prev).into(),
+            "}" => Action::Speed(SpeedAction::Faster).into(),
+            "{" => Action::Speed(SpeedAction::Slower).into(),
+            "n" => Action::Page(PageAction::Next).into(),
+            "p" => Action::Page(PageAction::Prev).into(),
+            "e" => Action::Exposure(ToneAction::Increase).into(),
+            "E" => Action::Exposure(ToneAction::Decrease).into(),
+            "y" => Action::Gamma(ToneAction::Increase).into(),
+            "Y" => Action::Gamma(ToneAction::Decrease).into(),
             ":" => Action::SwitchCommandMode.into(),
             _ => Action::Noop.into(),
         },
@@ -520,6 +949,7 @@ pub fn process_normal_mode<'a>(state: &mut State<'a>, event: &Event) -> ProcessA
                 Delete => Action::Delete.into(),
                 F11 => Action::ToggleFullscreen.into(),
                 Escape => Action::Quit.into(),
+                Tab => Action::SwitchGalleryMode.into(),
                 PageUp => Action::SkipForward.into(),
                 PageDown => Action::SkipBack.into(),
                 Home => Action::First.into(),
@@ -539,7 +969,13 @@ pub fn process_normal_mode<'a>(state: &mut State<'a>, event: &Event) -> ProcessA
             _ => Action::Noop.into(),
         },
 
-        Event::MouseButtonUp { mouse_btn: btn, .. } => match btn {
+        Event::MouseButtonUp {
+            mouse_btn: btn,
+            x,
+            y,
+            ..
+        } => match btn {
+            MouseButton::Left if state.render_filmstrip => Action::Click(*x, *y).into(),
             MouseButton::Left => Action::ToggleFit.into(),
             _ => Action::Noop.into(),
         },
@@ -547,6 +983,96 @@ pub fn process_normal_mode<'a>(state: &mut State<'a>, event: &Event) -> ProcessA
     }
 }
 
+/// Processes events in gallery mode, a count typed before an action repeats it that many times
+pub fn process_gallery_mode<'a>(state: &mut State<'a>, event: &Event) -> ProcessAction<'a> {
+    use sdl2::event::WindowEvent::*;
+    use sdl2::keyboard::Keycode::*;
+
+    let count = state.gallery.count;
+    let times = count.max(1);
+    let action: ProcessAction = match event {
+        Event::Quit { .. } => Action::Quit.into(),
+
+        Event::TextInput { text, .. } => match text.as_str() {
+            // 0 is not captured for first digit as it does not impact counts
+            "1" | "2" | "3" | "4" | "5" | "6" | "7" | "8" | "9" | "0" => {
+                // Safe to unwrap as only digits were matched
+                let digit = text.parse::<usize>().unwrap();
+                if count > 0 || digit > 0 {
+                    // Cap at highest possible value if overflow would occur
+                    state.gallery.count = count.saturating_mul(10).saturating_add(digit);
+                }
+                return Action::ReRender.into();
+            }
+            "c" => (Action::Copy, times).into(),
+            "d" => (Action::Trash, times).into(),
+            "D" => (Action::Delete, times).into(),
+            "f" => Action::ToggleFullscreen.into(),
+            "g" => Action::First.into(),
+            "G" if count > 0 => (Action::Select(SelectAction::Number), count).into(),
+            "G" => Action::Last.into(),
+            "h" => (Action::Select(SelectAction::Left), times).into(),
+            "j" => (Action::Select(SelectAction::Down), times).into(),
+            "k" => (Action::Select(SelectAction::Up), times).into(),
+            "l" => (Action::Select(SelectAction::Right), times).into(),
+            "m" => (Action::Move, times).into(),
+            "x" => (Action::Mark(MarkAction::Toggle), times).into(),
+            "X" => Action::Mark(MarkAction::Invert).into(),
+            "a" => Action::Mark(MarkAction::All).into(),
+            "u" => Action::Mark(MarkAction::Clear).into(),
+            "q" => Action::Quit.into(),
+            "?" => {
+                match state.render_help {
+                    HelpRender::Normal => state.render_help = HelpRender::None,
+                    _ => state.render_help = HelpRender::Normal,
+                }
+                return Action::ReRender.into();
+            }
+            "t" => {
+                state.render_infobar = !state.render_infobar;
+                return Action::ReRender.into();
+            }
+            _ => return Action::Noop.into(),
+        },
+
+        Event::KeyDown {
+            keycode: Some(k), ..
+        } => match k {
+            Escape if count > 0 => {
+                state.gallery.count = 0;
+                return Action::ReRender.into();
+            }
+            Backspace => {
+                state.gallery.count /= 10;
+                return Action::ReRender.into();
+            }
+            Return | Return2 | KpEnter | Tab | Escape => Action::SwitchNormalMode.into(),
+            Delete => (Action::Delete, times).into(),
+            F11 => Action::ToggleFullscreen.into(),
+            Home => Action::First.into(),
+            End => Action::Last.into(),
+            Left => (Action::Select(SelectAction::Left), times).into(),
+            Right => (Action::Select(SelectAction::Right), times).into(),
+            Up => (Action::Select(SelectAction::Up), times).into(),
+            Down => (Action::Select(SelectAction::Down), times).into(),
+            PageUp => (Action::Select(SelectAction::PageUp), times).into(),
+            PageDown => (Action::Select(SelectAction::PageDown), times).into(),
+            _ => return Action::Noop.into(),
+        },
+
+        Event::Window { win_event, .. } => match win_event {
+            // Exposed: Rerender if the window was not changed by us.
+            Exposed | Resized(..) | SizeChanged(..) | Maximized => return Action::ReRender.into(),
+            _ => return Action::Noop.into(),
+        },
+
+        _ => return Action::Noop.into(),
+    };
+    // The count is used up by the action it was typed for
+    state.gallery.count = 0;
+    action
+}
+
 /// Processes event information for Command mode, and returns them as Actions
 pub fn process_command_mode(event: &Event) -> Action {
     use sdl2::event::WindowEvent;
@@ -577,12 +1103,12 @@ pub fn process_command_mode(event: &Event) -> Action {
     }
 }
 
-/// Set zoom times to 1 if times is too big for i32 value or times is 0
-fn cap_zoom_times(times: usize) -> i32 {
-    let zoom_times = (times) as i32;
+/// Set zoom or speed times to 1 if times is too big for i32 value or times is 0
+fn cap_times(times: usize) -> i32 {
+    let capped_times = (times) as i32;
     // Malicious huge numbers overflow and 0 check
-    if zoom_times.is_positive() {
-        zoom_times
+    if capped_times.is_positive() {
+        capped_times
     } else {
         1
     }
@@ -590,7 +1116,13 @@ fn cap_zoom_times(times: usize) -> i32 {
 
 #[cfg(test)]
 mod tests {
-    use super::State;
+    use super::{
+        process_gallery_mode, process_normal_mode, Action, BookmarkAction, MarkAction, Playback,
+        RotAngle, SelectAction, State, Tone, MAX_EXPOSURE, MAX_GAMMA, MAX_SPEED, MIN_GAMMA,
+        MIN_SPEED,
+    };
+    use sdl2::event::Event;
+    use std::time::{Duration, Instant};
     #[test]
     fn test_zoom_in_and_then_out_gives_same_zoom_factor() {
         let mut state = State {
@@ -614,4 +1146,170 @@ mod tests {
         state.zoom_in(1);
         assert_eq!(state.scale, 1.0);
     }
+
+    #[test]
+    fn test_orient_rotates_and_flips_upright() {
+        let mut state = State {
+            ..Default::default()
+        };
+        state.orient(6);
+        assert_eq!(state.rot_angle, RotAngle::Right);
+        assert!(!state.flip_horizontal && !state.flip_vertical);
+        state.orient(5);
+        assert_eq!(state.rot_angle, RotAngle::Left);
+        assert!(state.flip_horizontal && !state.flip_vertical);
+        state.orient(9);
+        assert_eq!(state.rot_angle, RotAngle::Up);
+        assert!(!state.flip_horizontal && !state.flip_vertical);
+    }
+
+    #[test]
+    fn test_gallery_count_repeats_next_action() {
+        let mut state = State {
+            ..Default::default()
+        };
+        let text = |text: &str| Event::TextInput {
+            timestamp: 0,
+            window_id: 0,
+            text: text.to_string(),
+        };
+        process_gallery_mode(&mut state, &text("0"));
+        process_gallery_mode(&mut state, &text("1"));
+        process_gallery_mode(&mut state, &text("2"));
+        assert_eq!(state.gallery.count, 12);
+        let action = process_gallery_mode(&mut state, &text("j"));
+        assert!(matches!(action.action, Action::Select(SelectAction::Down)));
+        assert_eq!(action.times, 12);
+        assert_eq!(state.gallery.count, 0);
+        let action = process_gallery_mode(&mut state, &text("l"));
+        assert_eq!(action.times, 1);
+    }
+
+    #[test]
+    fn test_bookmark_keys_take_the_next_letter() {
+        let mut state = State {
+            ..Default::default()
+        };
+        let text = |text: &str| Event::TextInput {
+            timestamp: 0,
+            window_id: 0,
+            text: text.to_string(),
+        };
+        process_normal_mode(&mut state, &text("M"));
+        let action = process_normal_mode(&mut state, &text("a"));
+        assert!(matches!(
+            action.action,
+            Action::Bookmark(BookmarkAction::Set('a'))
+        ));
+        process_normal_mode(&mut state, &text("'"));
+        let action = process_normal_mode(&mut state, &text("a"));
+        assert!(matches!(
+            action.action,
+            Action::Bookmark(BookmarkAction::Jump('a'))
+        ));
+        // Anything but a lowercase letter cancels the bookmark
+        process_normal_mode(&mut state, &text("'"));
+        let action = process_normal_mode(&mut state, &text("1"));
+        assert!(matches!(action.action, Action::Noop));
+        let action = process_normal_mode(&mut state, &text("a"));
+        assert!(matches!(action.action, Action::Mark(MarkAction::All)));
+    }
+
+    #[test]
+    fn test_next_frame_wraps_to_first_frame() {
+        let mut playback = Playback::default();
+        playback.reset(5, None);
+        playback.next_frame(4);
+        assert_eq!(playback.frame, 4);
+        playback.next_frame(1);
+        assert_eq!(playback.frame, 0);
+        playback.next_frame(12);
+        assert_eq!(playback.frame, 2);
+    }
+
+    #[test]
+    fn test_prev_frame_wraps_to_last_frame() {
+        let mut playback = Playback::default();
+        playback.reset(5, None);
+        playback.prev_frame(1);
+        assert_eq!(playback.frame, 4);
+        playback.prev_frame(13);
+        assert_eq!(playback.frame, 1);
+    }
+
+    #[test]
+    fn test_speed_up_and_slow_down_are_capped() {
+        let mut playback = Playback::default();
+        playback.speed_up(2);
+        assert_eq!(playback.speed, 4.0);
+        playback.slow_down(1);
+        assert_eq!(playback.speed, 2.0);
+        playback.speed_up(100);
+        assert_eq!(playback.speed, MAX_SPEED);
+        playback.slow_down(100);
+        assert_eq!(playback.speed, MIN_SPEED);
+    }
+
+    #[test]
+    fn test_exposure_and_gamma_steps_are_capped() {
+        let mut tone = Tone::default();
+        tone.increase_exposure(3);
+        assert_eq!(tone.exposure, 1.5);
+        tone.decrease_exposure(5);
+        assert_eq!(tone.exposure, -1.0);
+        tone.decrease_exposure(100);
+        assert_eq!(tone.exposure, -MAX_EXPOSURE);
+        tone.increase_gamma(100);
+        assert_eq!(tone.gamma, MAX_GAMMA);
+        tone.decrease_gamma(100);
+        assert_eq!(tone.gamma, MIN_GAMMA);
+    }
+
+    #[test]
+    fn test_advance_moves_past_elapsed_frames() {
+        let mut playback = Playback::default();
+        playback.reset(4, None);
+        let start = playback.frame_start;
+        let delay = |_| Duration::from_millis(100);
+        assert!(!playback.advance(start + Duration::from_millis(50), delay));
+        assert!(playback.advance(start + Duration::from_millis(250), delay));
+        assert_eq!(playback.frame, 2);
+        assert_eq!(playback.frame_start, start + Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_advance_repeats_forever_without_plays() {
+        let mut playback = Playback::default();
+        playback.reset(2, None);
+        let start = playback.frame_start;
+        playback.advance(start + Duration::from_millis(1050), |_| {
+            Duration::from_millis(100)
+        });
+        assert_eq!(playback.frame, 0);
+        assert_eq!(playback.played, 5);
+        assert!(!playback.paused);
+    }
+
+    #[test]
+    fn test_advance_pauses_on_last_frame_after_last_play() {
+        let mut playback = Playback::default();
+        playback.reset(3, Some(2));
+        let now = Instant::now();
+        playback.frame_start = now - Duration::from_secs(10);
+        playback.advance(now, |_| Duration::from_millis(100));
+        assert_eq!(playback.frame, 2);
+        assert!(playback.paused);
+    }
+
+    #[test]
+    fn test_advance_respects_speed() {
+        let mut playback = Playback::default();
+        playback.reset(10, None);
+        playback.speed_up(1);
+        let start = playback.frame_start;
+        playback.advance(start + Duration::from_millis(100), |_| {
+            Duration::from_millis(100)
+        });
+        assert_eq!(playback.frame, 2);
+    }
 }